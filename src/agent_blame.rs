@@ -0,0 +1,237 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::DateTime;
+use similar::{DiffOp, TextDiff};
+
+use crate::changes::written_text;
+use crate::file_refs::get_paths_sessions;
+use crate::models::{
+    AgentBlame, BlameCommit, BlameLine, BlameSession, ContentBlock, ConversationMessage, FileAccess, FileRef,
+    FileSession, LineAuthor, MessageContent,
+};
+use crate::state::AppState;
+use crate::storage::{get_conversation, get_subagent_conversation};
+
+/// Upper bound for matching one written text against the file.
+const MATCH_TIMEOUT: Duration = Duration::from_millis(200);
+/// Alphanumeric characters a matched run needs somewhere, so lone `}` or
+/// blank lines aren't attributed to whichever edit also had one.
+const SIGNIFICANT_CHARS: usize = 3;
+const UNCOMMITTED_SHA: &str = "0000000000000000000000000000000000000000";
+
+struct BlameEntry {
+    commit: BlameCommit,
+    committer_time: i64,
+}
+
+/// `git blame --line-porcelain` of the working-tree file, one entry per line
+/// (None when uncommitted). None if git can't blame it, e.g. untracked.
+async fn git_blame(project: &Path, rel_path: &str) -> Option<Vec<Option<BlameEntry>>> {
+    let output = tokio::process::Command::new("git")
+        .args(["blame", "--line-porcelain", "--", rel_path])
+        .current_dir(project)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    let mut sha = String::new();
+    let (mut author, mut summary) = (String::new(), String::new());
+    let (mut time, mut committer_time) = (0, 0);
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if line.starts_with('\t') {
+            // The line's content ends its block
+            entries.push((sha != UNCOMMITTED_SHA).then(|| BlameEntry {
+                commit: BlameCommit {
+                    sha: sha.clone(),
+                    author: author.clone(),
+                    time,
+                    summary: summary.clone(),
+                },
+                committer_time,
+            }));
+        } else if let Some(value) = line.strip_prefix("author ") {
+            author = value.to_string();
+        } else if let Some(value) = line.strip_prefix("author-time ") {
+            time = value.parse().unwrap_or(0);
+        } else if let Some(value) = line.strip_prefix("committer-time ") {
+            committer_time = value.parse().unwrap_or(0);
+        } else if let Some(value) = line.strip_prefix("summary ") {
+            summary = value.to_string();
+        } else if let Some(first) = line.split(' ').next().filter(|s| s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit())) {
+            sha = first.to_string();
+        }
+    }
+    Some(entries)
+}
+
+/// One tool call's written text, split into lines.
+struct Candidate {
+    session_id: String,
+    file_ref: FileRef,
+    /// Seconds since the epoch
+    time: i64,
+    texts: Vec<Vec<String>>,
+}
+
+fn tool_uses(messages: &[ConversationMessage]) -> HashMap<&str, &ContentBlock> {
+    messages
+        .iter()
+        .filter(|m| m.msg_type == "assistant")
+        .filter_map(|m| match m.message.as_ref().and_then(|b| b.content.as_ref()) {
+            Some(MessageContent::Blocks(blocks)) => Some(blocks),
+            _ => None,
+        })
+        .flatten()
+        .filter(|b| b.block_type == "tool_use")
+        .filter_map(|b| Some((b.id.as_deref()?, b)))
+        .collect()
+}
+
+/// The Write/Edit calls of a session on the file, with the text they wrote.
+async fn session_candidates(state: &AppState, session: &FileSession) -> Vec<Candidate> {
+    let mut by_transcript: HashMap<Option<&str>, Vec<&FileRef>> = HashMap::new();
+    for file_ref in session.refs.iter().filter(|r| r.access == FileAccess::Write) {
+        by_transcript.entry(file_ref.agent_id.as_deref()).or_default().push(file_ref);
+    }
+
+    let mut candidates = Vec::new();
+    for (agent_id, refs) in by_transcript {
+        let messages = match agent_id {
+            Some(agent_id) => get_subagent_conversation(state, &session.session_id, agent_id).await,
+            None => get_conversation(state, &session.session_id, false).await,
+        };
+        let blocks = tool_uses(&messages);
+        for file_ref in refs {
+            let Some(block) = file_ref.tool_use_id.as_deref().and_then(|id| blocks.get(id)) else {
+                continue;
+            };
+            let (Some(name), Some(input)) = (block.name.as_deref(), block.input.as_ref()) else {
+                continue;
+            };
+            let Some((_, texts)) = written_text(name, input) else { continue };
+            let time = file_ref
+                .timestamp
+                .as_deref()
+                .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+                .map(|t| t.timestamp())
+                .unwrap_or(0);
+            candidates.push(Candidate {
+                session_id: session.session_id.clone(),
+                file_ref: file_ref.clone(),
+                time,
+                texts: texts.iter().map(|t| t.lines().map(String::from).collect()).collect(),
+            });
+        }
+    }
+    candidates
+}
+
+fn is_significant(line: &str) -> bool {
+    line.chars().filter(|c| c.is_alphanumeric()).count() >= SIGNIFICANT_CHARS
+}
+
+/// Blame a file inside `project` (both canonical), attributing each line to
+/// the latest recorded Write/Edit whose text contains it. A committed line is
+/// only attributed to calls made before its commit. `aliases` are other
+/// absolute paths sessions may have used for the file, e.g. through symlinks.
+pub async fn agent_blame(state: &AppState, project: &Path, file: &Path, aliases: &[String]) -> std::io::Result<AgentBlame> {
+    let content = tokio::fs::read_to_string(file).await?;
+    let file_lines: Vec<&str> = content.lines().collect();
+    let rel_path = file.strip_prefix(project).unwrap_or(file).to_string_lossy().to_string();
+    let blame = git_blame(project, &rel_path).await.unwrap_or_default();
+
+    let path = file.to_string_lossy().to_string();
+    let mut paths = vec![path.clone()];
+    for alias in aliases.iter().filter(|a| Path::new(a).is_absolute()) {
+        if !paths.contains(alias) {
+            paths.push(alias.clone());
+        }
+    }
+    let paths: Vec<&str> = paths.iter().map(|p| p.as_str()).collect();
+    let mut sessions: Vec<FileSession> = get_paths_sessions(state, &paths).await;
+
+    // A call seen through several aliases is replayed once
+    for session in &mut sessions {
+        let mut seen = HashSet::new();
+        session
+            .refs
+            .retain(|r| r.tool_use_id.as_ref().is_none_or(|id| seen.insert(id.clone())));
+    }
+
+    let mut candidates = Vec::new();
+    for session in &sessions {
+        candidates.extend(session_candidates(state, session).await);
+    }
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.time));
+
+    let mut authors: Vec<Option<&Candidate>> = vec![None; file_lines.len()];
+    for candidate in &candidates {
+        for text in &candidate.texts {
+            let text: Vec<&str> = text.iter().map(|l| l.as_str()).collect();
+            let diff = TextDiff::configure().timeout(MATCH_TIMEOUT).diff_slices(&text, &file_lines);
+            for op in diff.ops() {
+                let DiffOp::Equal { new_index, len, .. } = *op else { continue };
+                let run = new_index..new_index + len;
+                if !file_lines[run.clone()].iter().any(|l| is_significant(l)) {
+                    continue;
+                }
+                for i in run {
+                    let committed_before = blame
+                        .get(i)
+                        .and_then(|e| e.as_ref())
+                        .is_some_and(|e| e.committer_time < candidate.time);
+                    if authors[i].is_none() && !committed_before {
+                        authors[i] = Some(candidate);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for candidate in authors.iter().flatten() {
+        *counts.entry(candidate.session_id.as_str()).or_default() += 1;
+    }
+    let mut blame_sessions: Vec<BlameSession> = sessions
+        .iter()
+        .filter_map(|s| {
+            Some(BlameSession {
+                session_id: s.session_id.clone(),
+                display: s.display.clone(),
+                lines: *counts.get(s.session_id.as_str())?,
+            })
+        })
+        .collect();
+    blame_sessions.sort_by_key(|s| std::cmp::Reverse(s.lines));
+
+    let mut blame = blame.into_iter();
+    let lines = file_lines
+        .iter()
+        .zip(authors)
+        .enumerate()
+        .map(|(i, (line, author))| BlameLine {
+            line: i + 1,
+            content: line.to_string(),
+            commit: blame.next().flatten().map(|e| e.commit),
+            session: author.map(|c| LineAuthor {
+                session_id: c.session_id.clone(),
+                tool: c.file_ref.tool.clone(),
+                message_uuid: c.file_ref.uuid.clone(),
+                timestamp: c.file_ref.timestamp.clone(),
+                agent_id: c.file_ref.agent_id.clone(),
+            }),
+        })
+        .collect();
+
+    Ok(AgentBlame {
+        path,
+        lines,
+        sessions: blame_sessions,
+    })
+}
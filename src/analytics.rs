@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::models::UsageTotals;
+use crate::session_meta::{get_session_meta, get_subagent_metas, SessionMeta};
+use crate::state::AppState;
+use crate::storage::{get_project_name, project_path_from_file};
+
+/// USD per million tokens.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    pub cache_write: f64,
+    pub cache_read: f64,
+}
+
+impl ModelPrice {
+    fn cost(&self, usage: &UsageTotals) -> f64 {
+        (usage.input_tokens as f64 * self.input
+            + usage.output_tokens as f64 * self.output
+            + usage.cache_creation_input_tokens as f64 * self.cache_write
+            + usage.cache_read_input_tokens as f64 * self.cache_read)
+            / 1_000_000.0
+    }
+}
+
+/// Written to `pricing.json` on first use; keys are model name prefixes.
+const DEFAULT_PRICING: &[(&str, ModelPrice)] = &[
+    ("claude-opus-4-5", ModelPrice { input: 5.0, output: 25.0, cache_write: 6.25, cache_read: 0.5 }),
+    ("claude-opus-4", ModelPrice { input: 15.0, output: 75.0, cache_write: 18.75, cache_read: 1.5 }),
+    ("claude-sonnet-4", ModelPrice { input: 3.0, output: 15.0, cache_write: 3.75, cache_read: 0.3 }),
+    ("claude-3-7-sonnet", ModelPrice { input: 3.0, output: 15.0, cache_write: 3.75, cache_read: 0.3 }),
+    ("claude-3-5-sonnet", ModelPrice { input: 3.0, output: 15.0, cache_write: 3.75, cache_read: 0.3 }),
+    ("claude-haiku-4-5", ModelPrice { input: 1.0, output: 5.0, cache_write: 1.25, cache_read: 0.1 }),
+    ("claude-3-5-haiku", ModelPrice { input: 0.8, output: 4.0, cache_write: 1.0, cache_read: 0.08 }),
+];
+
+fn pricing_path(state: &AppState) -> String {
+    format!("{}/pricing.json", state.claude_dir)
+}
+
+/// Load the user-editable pricing table, creating it with defaults if missing.
+pub async fn load_pricing(state: &AppState) -> HashMap<String, ModelPrice> {
+    let path = pricing_path(state);
+    match fs::read_to_string(&path).await {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(pricing) => return pricing,
+            Err(e) => eprintln!("[analytics] invalid {}: {}, using defaults", path, e),
+        },
+        Err(_) => {
+            let defaults: BTreeMap<&str, ModelPrice> = DEFAULT_PRICING.iter().copied().collect();
+            if let Ok(json) = serde_json::to_string_pretty(&defaults) {
+                let _ = fs::write(&path, json).await;
+            }
+        }
+    }
+    DEFAULT_PRICING
+        .iter()
+        .map(|(model, price)| (model.to_string(), *price))
+        .collect()
+}
+
+/// Price for a model: the entry with the longest matching prefix.
+fn price_for<'a>(pricing: &'a HashMap<String, ModelPrice>, model: &str) -> Option<&'a ModelPrice> {
+    pricing
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| price)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Session,
+    Project,
+    Model,
+    Day,
+}
+
+pub struct AnalyticsFilter {
+    pub group: GroupBy,
+    /// Inclusive YYYY-MM-DD bounds
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsRow {
+    pub key: String,
+    /// Human-readable name for the key (session prompt, project name)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(flatten)]
+    pub usage: UsageTotals,
+    pub cost: f64,
+}
+
+impl AnalyticsRow {
+    fn add(&mut self, usage: &UsageTotals, cost: f64) {
+        self.usage.add(usage);
+        self.cost += cost;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsResult {
+    pub rows: Vec<AnalyticsRow>,
+    pub total: AnalyticsRow,
+    /// Models with usage but no pricing entry (counted at zero cost)
+    pub unpriced_models: Vec<String>,
+}
+
+/// Token usage and estimated cost across all sessions (subagents included),
+/// grouped by session, project, model or day. Sessions in the trash are left out.
+pub async fn get_analytics(state: &AppState, filter: &AnalyticsFilter) -> AnalyticsResult {
+    let pricing = load_pricing(state).await;
+    let sessions: Vec<(String, String)> = state
+        .file_index
+        .iter()
+        .filter(|e| !state.hidden_sessions.contains_key(e.key()))
+        .map(|e| (e.key().clone(), e.value().clone()))
+        .collect();
+
+    let mut rows: HashMap<String, AnalyticsRow> = HashMap::new();
+    let mut total = AnalyticsRow {
+        key: "total".to_string(),
+        ..Default::default()
+    };
+    let mut unpriced = BTreeSet::new();
+
+    for (session_id, file_path) in sessions {
+        let meta = match get_session_meta(state, &session_id).await {
+            Some(m) => m,
+            None => continue,
+        };
+        let project = meta.cwd.clone().unwrap_or_else(|| project_path_from_file(&file_path));
+        if let Some(ref wanted) = filter.project {
+            if &project != wanted {
+                continue;
+            }
+        }
+        let mut metas: Vec<SessionMeta> = get_subagent_metas(state, &session_id).await;
+        metas.push(meta.clone());
+
+        for m in &metas {
+            for (day, models) in &m.usage {
+                if filter.from.as_ref().is_some_and(|from| day < from)
+                    || filter.to.as_ref().is_some_and(|to| day > to)
+                {
+                    continue;
+                }
+                for (model, usage) in models {
+                    let cost = match price_for(&pricing, model) {
+                        Some(price) => price.cost(usage),
+                        None => {
+                            unpriced.insert(model.clone());
+                            0.0
+                        }
+                    };
+                    let (key, label) = match filter.group {
+                        GroupBy::Session => (session_id.clone(), meta.first_prompt.clone()),
+                        GroupBy::Project => (project.clone(), Some(get_project_name(&project))),
+                        GroupBy::Model => (model.clone(), None),
+                        GroupBy::Day => (day.clone(), None),
+                    };
+                    rows.entry(key.clone())
+                        .or_insert_with(|| AnalyticsRow {
+                            key,
+                            label,
+                            ..Default::default()
+                        })
+                        .add(usage, cost);
+                    total.add(usage, cost);
+                }
+            }
+        }
+    }
+
+    let mut rows: Vec<AnalyticsRow> = rows.into_values().collect();
+    match filter.group {
+        GroupBy::Day => rows.sort_by(|a, b| a.key.cmp(&b.key)),
+        _ => rows.sort_by(|a, b| b.cost.partial_cmp(&a.cost).unwrap_or(std::cmp::Ordering::Equal)),
+    }
+
+    AnalyticsResult {
+        rows,
+        total,
+        unpriced_models: unpriced.into_iter().collect(),
+    }
+}
@@ -0,0 +1,422 @@
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::compression::{read_transcript, session_dir};
+use crate::session_meta::get_session_meta;
+use crate::state::AppState;
+use crate::storage::{encode_project_path, find_session_file, get_session_project};
+use crate::summarizer::summary_path;
+
+const BUNDLE_VERSION: u32 = 1;
+/// Upper bound on the unpacked size of an imported bundle.
+const MAX_UNPACKED_SIZE: u64 = 1 << 30;
+
+/// `manifest.json` at the root of a bundle.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleManifest {
+    version: u32,
+    session_id: String,
+    /// Project path on the exporting machine
+    project: Option<String>,
+    slug: Option<String>,
+    exported_at: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub session_id: String,
+    pub project: String,
+    pub file_path: String,
+    pub subagents: usize,
+    pub plans: usize,
+}
+
+/// Session ids and subagent/plan file names end up in paths, so only plain
+/// names are accepted.
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+fn append_file(builder: &mut tar::Builder<GzEncoder<Vec<u8>>>, path: &str, data: &[u8]) -> Result<(), String> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, path, data)
+        .map_err(|e| format!("Failed to add {}: {}", path, e))
+}
+
+/// Files of a directory whose names pass `filter`, sorted by name.
+async fn read_dir_files(dir: &str, filter: impl Fn(&str) -> bool) -> Vec<(String, Vec<u8>)> {
+    let mut files = Vec::new();
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(e) => e,
+        Err(_) => return files,
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !filter(&name) || !entry.file_type().await.is_ok_and(|t| t.is_file()) {
+            continue;
+        }
+        if let Ok(data) = fs::read(entry.path()).await {
+            files.push((name, data));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files
+}
+
+/// Pack a session into a `.tar.gz`: the transcript, its subagent transcripts,
+/// the plan files for its slug and the claude-run summary. Everything sits
+/// under a `<sessionId>/` directory.
+pub async fn build_bundle(state: &AppState, session_id: &str) -> Result<Vec<u8>, String> {
+    let file_path = find_session_file(state, session_id)
+        .await
+        .ok_or_else(|| "Session not found".to_string())?;
+    let transcript = read_transcript(&file_path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+    let meta = get_session_meta(state, session_id).await;
+    let slug = meta.and_then(|m| m.slug);
+
+    let subagents = read_dir_files(
+        &format!("{}/subagents", session_dir(&file_path)),
+        |name| name.ends_with(".jsonl"),
+    )
+    .await;
+    let plans = match slug {
+        Some(ref slug) => {
+            // `<slug>.md` and the session's subagent plans, `<slug>-agent-<id>.md`
+            let agent_prefix = format!("{}-agent-", slug);
+            read_dir_files(&format!("{}/plans", state.session_source(session_id).claude_dir), |name| {
+                name.strip_suffix(".md")
+                    .is_some_and(|stem| stem == slug || stem.starts_with(&agent_prefix))
+            })
+            .await
+        }
+        None => Vec::new(),
+    };
+    let summary = fs::read(summary_path(state, session_id)).await.ok();
+
+    let manifest = BundleManifest {
+        version: BUNDLE_VERSION,
+        session_id: session_id.to_string(),
+        project: get_session_project(state, session_id).await,
+        slug,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    append_file(&mut builder, &format!("{}/manifest.json", session_id), &manifest)?;
+    append_file(&mut builder, &format!("{}/session.jsonl", session_id), &transcript)?;
+    for (name, data) in &subagents {
+        append_file(&mut builder, &format!("{}/subagents/{}", session_id, name), data)?;
+    }
+    for (name, data) in &plans {
+        append_file(&mut builder, &format!("{}/plans/{}", session_id, name), data)?;
+    }
+    if let Some(ref summary) = summary {
+        append_file(&mut builder, &format!("{}/summary", session_id), summary)?;
+    }
+    builder
+        .into_inner()
+        .and_then(|gz| gz.finish())
+        .map_err(|e| format!("Failed to write bundle: {}", e))
+}
+
+/// Point `cwd` fields under `from` at `to`, so `claude --resume` finds the
+/// session from the new project directory. Unparseable lines are kept as is.
+fn rebase_cwd(content: &[u8], from: &str, to: &str) -> Vec<u8> {
+    if from == to {
+        return content.to_vec();
+    }
+    let mut out = Vec::with_capacity(content.len());
+    for line in content.split_inclusive(|&b| b == b'\n') {
+        let body = line.strip_suffix(b"\n").unwrap_or(line);
+        let mut val = match serde_json::from_slice::<serde_json::Value>(body) {
+            Ok(v) => v,
+            Err(_) => {
+                out.extend_from_slice(line);
+                continue;
+            }
+        };
+        let rebased = val.get("cwd").and_then(|c| c.as_str()).and_then(|cwd| {
+            let rest = cwd.strip_prefix(from)?;
+            (rest.is_empty() || rest.starts_with('/')).then(|| format!("{}{}", to, rest))
+        });
+        match rebased {
+            Some(cwd) => {
+                val["cwd"] = serde_json::Value::String(cwd);
+                out.extend_from_slice(&serde_json::to_vec(&val).unwrap_or_else(|_| body.to_vec()));
+                out.push(b'\n');
+            }
+            None => out.extend_from_slice(line),
+        }
+    }
+    out
+}
+
+struct UnpackedBundle {
+    manifest: BundleManifest,
+    transcript: Vec<u8>,
+    subagents: Vec<(String, Vec<u8>)>,
+    plans: Vec<(String, Vec<u8>)>,
+    summary: Option<String>,
+}
+
+fn unpack(data: &[u8]) -> Result<UnpackedBundle, String> {
+    let mut archive = tar::Archive::new(GzDecoder::new(data));
+    let mut manifest = None;
+    let mut transcript = None;
+    let mut subagents = Vec::new();
+    let mut plans = Vec::new();
+    let mut summary = None;
+    let mut total: u64 = 0;
+
+    let entries = archive.entries().map_err(|e| format!("Invalid bundle: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Invalid bundle: {}", e))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        total += entry.size();
+        if total > MAX_UNPACKED_SIZE {
+            return Err("Bundle is too large".to_string());
+        }
+        let path = entry
+            .path()
+            .map_err(|e| format!("Invalid bundle: {}", e))?
+            .to_string_lossy()
+            .to_string();
+        let mut buf = Vec::new();
+        entry
+            .read_to_end(&mut buf)
+            .map_err(|e| format!("Invalid bundle: {}", e))?;
+
+        // Strip the `<sessionId>/` root directory
+        let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).skip(1).collect();
+        match parts.as_slice() {
+            ["manifest.json"] => {
+                manifest = Some(
+                    serde_json::from_slice::<BundleManifest>(&buf)
+                        .map_err(|e| format!("Invalid manifest: {}", e))?,
+                )
+            }
+            ["session.jsonl"] => transcript = Some(buf),
+            ["summary"] => summary = Some(String::from_utf8_lossy(&buf).to_string()),
+            ["subagents", name] if is_safe_name(name) && name.ends_with(".jsonl") => {
+                subagents.push((name.to_string(), buf))
+            }
+            ["plans", name] if is_safe_name(name) && name.ends_with(".md") => plans.push((name.to_string(), buf)),
+            _ => {}
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| "Bundle has no manifest.json".to_string())?;
+    if manifest.version > BUNDLE_VERSION {
+        return Err(format!("Unsupported bundle version {}", manifest.version));
+    }
+    if !is_safe_name(&manifest.session_id) {
+        return Err("Invalid session id in manifest".to_string());
+    }
+    let transcript = transcript.ok_or_else(|| "Bundle has no session.jsonl".to_string())?;
+    Ok(UnpackedBundle {
+        manifest,
+        transcript,
+        subagents,
+        plans,
+        summary,
+    })
+}
+
+/// Unpack a bundle into `projects/<encoded project>/` and register it, so it
+/// shows up in the session list and can be resumed. `project` defaults to the
+/// project path recorded in the bundle.
+pub async fn import_bundle(state: &AppState, data: &[u8], project: Option<&str>) -> Result<ImportResult, String> {
+    let bundle = unpack(data)?;
+    let session_id = bundle.manifest.session_id.clone();
+    let source_project = bundle.manifest.project.clone().unwrap_or_default();
+    let project = project
+        .map(|p| p.trim_end_matches('/').to_string())
+        .filter(|p| !p.is_empty())
+        .or(bundle.manifest.project.clone())
+        .ok_or_else(|| "No project path given and none recorded in the bundle".to_string())?;
+    if !project.starts_with('/') {
+        return Err(format!("Project path must be absolute: {}", project));
+    }
+
+    if find_session_file(state, &session_id).await.is_some() {
+        return Err(format!("Session {} already exists", session_id));
+    }
+    let project_dir = format!("{}/{}", state.projects_dir, encode_project_path(&project));
+    let file_path = format!("{}/{}.jsonl", project_dir, session_id);
+    if fs::metadata(&file_path).await.is_ok() {
+        return Err(format!("Session {} already exists", session_id));
+    }
+
+    let rebase = |content: &[u8]| {
+        if source_project.is_empty() {
+            content.to_vec()
+        } else {
+            rebase_cwd(content, &source_project, &project)
+        }
+    };
+
+    // Subagents and plans first: the watcher picks the session up as soon as
+    // the transcript appears.
+    if !bundle.subagents.is_empty() {
+        let subagents_dir = format!("{}/{}/subagents", project_dir, session_id);
+        fs::create_dir_all(&subagents_dir)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", subagents_dir, e))?;
+        for (name, content) in &bundle.subagents {
+            fs::write(format!("{}/{}", subagents_dir, name), rebase(content))
+                .await
+                .map_err(|e| format!("Failed to write subagent {}: {}", name, e))?;
+        }
+    }
+    let plans_dir = format!("{}/plans", state.claude_dir);
+    let _ = fs::create_dir_all(&plans_dir).await;
+    for (name, content) in &bundle.plans {
+        let path = format!("{}/{}", plans_dir, name);
+        // Never clobber a local plan with the same slug
+        if fs::metadata(&path).await.is_err() {
+            let _ = fs::write(&path, content).await;
+        }
+    }
+    if let Some(ref summary) = bundle.summary {
+        let path = summary_path(state, &session_id);
+        if let Some(dir) = Path::new(&path).parent() {
+            fs::create_dir_all(dir)
+                .await
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        fs::write(&path, summary)
+            .await
+            .map_err(|e| format!("Failed to write summary: {}", e))?;
+        let mut lines = summary.lines();
+        let msg_count: usize = lines.next().and_then(|l| l.parse().ok()).unwrap_or(0);
+        let text = lines.collect::<Vec<_>>().join("\n");
+        if !text.is_empty() {
+            state.summary_cache.insert(session_id.clone(), (text, msg_count));
+        }
+    }
+
+    let tmp_path = format!("{}.tmp", file_path);
+    fs::create_dir_all(&project_dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", project_dir, e))?;
+    fs::write(&tmp_path, rebase(&bundle.transcript))
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", file_path, e))?;
+    fs::rename(&tmp_path, &file_path)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", file_path, e))?;
+
+    state.file_index.insert(session_id.clone(), file_path.clone());
+    state.invalidate_history_cache();
+    eprintln!("[bundle] imported {} into {}", session_id, project);
+
+    Ok(ImportResult {
+        session_id,
+        project,
+        file_path,
+        subagents: bundle.subagents.len(),
+        plans: bundle.plans.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Build a bundle with raw entry names, bypassing the path checks of
+    /// `tar::Builder`. `size` overrides the size recorded in the header.
+    fn raw_bundle(entries: &[(&str, &[u8], Option<u64>)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for (name, data, size) in entries {
+            let mut header = tar::Header::new_gnu();
+            let raw = &mut header.as_gnu_mut().unwrap().name;
+            raw[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(size.unwrap_or(data.len() as u64));
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        let mut gz = builder.into_inner().unwrap();
+        gz.flush().unwrap();
+        gz.finish().unwrap()
+    }
+
+    const MANIFEST: &[u8] = br#"{"version":1,"sessionId":"abc","project":"/src/app","slug":null,"exportedAt":"2026-01-01T00:00:00Z"}"#;
+
+    #[test]
+    fn unpack_ignores_traversal_and_absolute_names() {
+        let data = raw_bundle(&[
+            ("abc/manifest.json", MANIFEST, None),
+            ("abc/session.jsonl", b"{}\n", None),
+            ("abc/plans/../../../evil.md", b"x", None),
+            ("abc/plans/..", b"x", None),
+            ("abc/subagents/../agent-1.jsonl", b"x", None),
+            ("/etc/cron.d/evil", b"x", None),
+            ("abc/plans/ok.md", b"plan", None),
+        ]);
+        let bundle = unpack(&data).unwrap();
+        assert_eq!(bundle.manifest.session_id, "abc");
+        assert!(bundle.subagents.is_empty());
+        assert_eq!(bundle.plans, vec![("ok.md".to_string(), b"plan".to_vec())]);
+    }
+
+    #[test]
+    fn unpack_rejects_unsafe_session_id() {
+        let manifest = br#"{"version":1,"sessionId":"../abc","exportedAt":"2026-01-01T00:00:00Z"}"#;
+        let data = raw_bundle(&[("abc/manifest.json", manifest, None), ("abc/session.jsonl", b"{}\n", None)]);
+        assert!(unpack(&data).is_err());
+    }
+
+    #[test]
+    fn unpack_rejects_oversized_bundles() {
+        // The header claims more than the cap; the check runs before reading
+        let data = raw_bundle(&[
+            ("abc/manifest.json", MANIFEST, None),
+            ("abc/session.jsonl", b"", Some(MAX_UNPACKED_SIZE + 1)),
+        ]);
+        assert_eq!(unpack(&data).err().as_deref(), Some("Bundle is too large"));
+    }
+
+    #[test]
+    fn rebase_cwd_rewrites_paths_under_the_source_project() {
+        let content = concat!(
+            r#"{"type":"user","cwd":"/src/app"}"#, "\n",
+            r#"{"type":"user","cwd":"/src/app/web"}"#, "\n",
+            r#"{"type":"user","cwd":"/src/app2"}"#, "\n",
+            "not json\n",
+            r#"{"type":"summary"}"#,
+        );
+        let out = String::from_utf8(rebase_cwd(content.as_bytes(), "/src/app", "/home/me/app")).unwrap();
+        let lines: Vec<&str> = out.split('\n').collect();
+        let cwd = |line: &str| serde_json::from_str::<serde_json::Value>(line).unwrap()["cwd"].clone();
+        assert_eq!(cwd(lines[0]), "/home/me/app");
+        assert_eq!(cwd(lines[1]), "/home/me/app/web");
+        assert_eq!(lines[2], r#"{"type":"user","cwd":"/src/app2"}"#);
+        assert_eq!(lines[3], "not json");
+        assert_eq!(lines[4], r#"{"type":"summary"}"#);
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use similar::TextDiff;
+
+use crate::models::{ConversationMessage, FileChange, FileChanges, MessageContent, SessionChanges};
+use crate::state::AppState;
+use crate::storage::{get_conversation, get_session_project, get_subagent_conversation, get_subagent_map};
+
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Deserialize)]
+struct EditInput {
+    #[serde(default)]
+    old_string: String,
+    #[serde(default)]
+    new_string: String,
+    #[serde(default)]
+    replace_all: bool,
+}
+
+enum Op {
+    Write(String),
+    Edit(Vec<EditInput>),
+    Notebook { cell_id: Option<String>, new_source: String },
+}
+
+/// A successful file-changing tool call, before replay.
+struct Recorded {
+    path: String,
+    op: Op,
+    change: FileChange,
+    /// File content before the call, when the transcript recorded it
+    original: Option<String>,
+    /// Write reported creating the file
+    created: bool,
+}
+
+fn parse_op(name: &str, input: &serde_json::Value) -> Option<(String, Op)> {
+    let str_field = |key: &str| input.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+    match name {
+        "Write" => Some((str_field("file_path")?, Op::Write(str_field("content").unwrap_or_default()))),
+        "Edit" => {
+            let edit: EditInput = serde_json::from_value(input.clone()).ok()?;
+            Some((str_field("file_path")?, Op::Edit(vec![edit])))
+        }
+        "MultiEdit" => {
+            let edits: Vec<EditInput> = serde_json::from_value(input.get("edits")?.clone()).ok()?;
+            Some((str_field("file_path")?, Op::Edit(edits)))
+        }
+        "NotebookEdit" => Some((
+            str_field("notebook_path")?,
+            Op::Notebook {
+                cell_id: str_field("cell_id"),
+                new_source: str_field("new_source").unwrap_or_default(),
+            },
+        )),
+        _ => None,
+    }
+}
+
+/// The file a tool call changed and the text it wrote there: the whole
+/// content for Write, each `new_string` for Edit and MultiEdit.
+pub fn written_text(tool: &str, input: &serde_json::Value) -> Option<(String, Vec<String>)> {
+    let (path, op) = parse_op(tool, input)?;
+    let texts = match op {
+        Op::Write(content) => vec![content],
+        Op::Edit(edits) => edits.into_iter().map(|e| e.new_string).collect(),
+        Op::Notebook { new_source, .. } => vec![new_source],
+    };
+    Some((path, texts))
+}
+
+/// File-changing tool calls of one transcript whose tool_result succeeded.
+fn collect(messages: &[ConversationMessage], agent_id: Option<&str>) -> Vec<Recorded> {
+    // tool_use_id → (is_error, toolUseResult)
+    let mut results: HashMap<&str, (bool, Option<&serde_json::Value>)> = HashMap::new();
+    for msg in messages.iter().filter(|m| m.msg_type == "user") {
+        let Some(MessageContent::Blocks(blocks)) = msg.message.as_ref().and_then(|m| m.content.as_ref()) else {
+            continue;
+        };
+        for block in blocks.iter().filter(|b| b.block_type == "tool_result") {
+            if let Some(id) = block.tool_use_id.as_deref() {
+                results.insert(id, (block.is_error.unwrap_or(false), msg.extra.get("toolUseResult")));
+            }
+        }
+    }
+
+    let mut recorded = Vec::new();
+    for msg in messages.iter().filter(|m| m.msg_type == "assistant") {
+        let Some(MessageContent::Blocks(blocks)) = msg.message.as_ref().and_then(|m| m.content.as_ref()) else {
+            continue;
+        };
+        for block in blocks.iter().filter(|b| b.block_type == "tool_use") {
+            let (Some(id), Some(name), Some(input)) = (block.id.as_deref(), block.name.as_deref(), block.input.as_ref()) else {
+                continue;
+            };
+            let Some((path, op)) = parse_op(name, input) else { continue };
+            let tool_result = match results.get(id) {
+                Some((false, tool_result)) => *tool_result,
+                _ => continue,
+            };
+            recorded.push(Recorded {
+                path,
+                op,
+                change: FileChange {
+                    tool_use_id: id.to_string(),
+                    tool: name.to_string(),
+                    agent_id: agent_id.map(|a| a.to_string()),
+                    message_uuid: msg.uuid.clone(),
+                    timestamp: msg.timestamp.clone(),
+                    diff: String::new(),
+                },
+                original: tool_result
+                    .and_then(|r| r.get("originalFile"))
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                created: tool_result.and_then(|r| r.get("type")).and_then(|v| v.as_str()) == Some("create"),
+            });
+        }
+    }
+    recorded
+}
+
+/// Apply Edit/MultiEdit replacements the way the tools do. None if an
+/// `old_string` isn't there, i.e. the file changed outside the session.
+fn apply_edits(content: &str, edits: &[EditInput]) -> Option<String> {
+    let mut content = content.to_string();
+    for edit in edits {
+        if edit.old_string.is_empty() {
+            // An empty old_string creates the file
+            if !content.is_empty() {
+                return None;
+            }
+            content = edit.new_string.clone();
+        } else if !content.contains(&edit.old_string) {
+            return None;
+        } else if edit.replace_all {
+            content = content.replace(&edit.old_string, &edit.new_string);
+        } else {
+            content = content.replacen(&edit.old_string, &edit.new_string, 1);
+        }
+    }
+    Some(content)
+}
+
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_name, new_name)
+        .to_string()
+}
+
+/// Diff of the edited snippets alone, for when the file content is unknown.
+fn snippet_diff(op: &Op, a_name: &str, b_name: &str) -> String {
+    match op {
+        Op::Write(content) => unified_diff("", content, a_name, b_name),
+        Op::Edit(edits) => edits
+            .iter()
+            .map(|e| unified_diff(&e.old_string, &e.new_string, a_name, b_name))
+            .collect(),
+        Op::Notebook { cell_id, new_source } => {
+            let cell = cell_id.as_deref().unwrap_or("new");
+            let (a_cell, b_cell) = (format!("{} (cell {})", a_name, cell), format!("{} (cell {})", b_name, cell));
+            unified_diff("", new_source, &a_cell, &b_cell)
+        }
+    }
+}
+
+/// Old and new line counts of a `@@ -a,b +c,d @@` hunk header.
+fn hunk_counts(line: &str) -> Option<(usize, usize)> {
+    let (ranges, _) = line.strip_prefix("@@ -")?.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let count = |range: &str| range.split_once(',').map_or(Some(1), |(_, n)| n.parse().ok());
+    Some((count(old)?, count(new)?))
+}
+
+/// Added and removed lines of a unified diff (possibly several concatenated).
+/// Lines are counted only inside hunks, so `---`/`+++` file headers are
+/// skipped but removed or added lines starting with `--`/`++` are not.
+fn diff_stats(diff: &str) -> (usize, usize) {
+    let (mut added, mut removed) = (0, 0);
+    // Lines still expected in the current hunk, old and new side
+    let (mut old_left, mut new_left) = (0usize, 0usize);
+    for line in diff.lines() {
+        if old_left == 0 && new_left == 0 {
+            if let Some((old, new)) = hunk_counts(line) {
+                (old_left, new_left) = (old, new);
+            }
+            continue;
+        }
+        match line.as_bytes().first() {
+            Some(b'+') => {
+                added += 1;
+                new_left = new_left.saturating_sub(1);
+            }
+            Some(b'-') => {
+                removed += 1;
+                old_left = old_left.saturating_sub(1);
+            }
+            Some(b'\\') => {}
+            _ => {
+                old_left = old_left.saturating_sub(1);
+                new_left = new_left.saturating_sub(1);
+            }
+        }
+    }
+    (added, removed)
+}
+
+/// Replay one file's changes in order, rebuilding its content from the first
+/// recorded `originalFile` (or an empty file for a created one).
+fn replay(path: String, recorded: Vec<Recorded>, project: Option<&str>) -> FileChanges {
+    let name = project
+        .and_then(|p| path.strip_prefix(&format!("{}/", p.trim_end_matches('/'))))
+        .unwrap_or(&path)
+        .to_string();
+    let (a_name, b_name) = (format!("a/{}", name.trim_start_matches('/')), format!("b/{}", name.trim_start_matches('/')));
+
+    let created = recorded.first().is_some_and(|r| r.created);
+    let mut base: Option<String> = None;
+    let mut current: Option<String> = None;
+    let mut complete = true;
+    let mut changes = Vec::new();
+
+    for (i, r) in recorded.into_iter().enumerate() {
+        if current.is_none() {
+            current = r.original.clone().or_else(|| r.created.then(String::new));
+            if i == 0 {
+                base = current.clone();
+            }
+        }
+        let before = current.take();
+        let after = match (&r.op, &before) {
+            (Op::Write(content), _) => Some(content.clone()),
+            (Op::Edit(edits), Some(before)) => apply_edits(before, edits),
+            _ => None,
+        };
+
+        let mut change = r.change;
+        match (&before, &after) {
+            (Some(before), Some(after)) => change.diff = unified_diff(before, after, &a_name, &b_name),
+            _ => {
+                change.diff = snippet_diff(&r.op, &a_name, &b_name);
+                complete = false;
+            }
+        }
+        changes.push(change);
+        current = after;
+    }
+
+    let complete = complete && base.is_some() && current.is_some();
+    let diff = match (&base, &current) {
+        (Some(base), Some(current)) if complete => {
+            let old_name = if created { "/dev/null".to_string() } else { a_name };
+            unified_diff(base, current, &old_name, &b_name)
+        }
+        _ => changes.iter().map(|c| c.diff.as_str()).collect(),
+    };
+    let (additions, deletions) = diff_stats(&diff);
+
+    FileChanges {
+        path,
+        changes,
+        diff,
+        complete,
+        created,
+        additions,
+        deletions,
+    }
+}
+
+/// Every file change made by a session and its subagents, replayed into one
+/// diff per file. Only calls whose tool_result succeeded count, and only on
+/// the active branch of the main transcript.
+pub async fn get_session_changes(state: &AppState, session_id: &str) -> SessionChanges {
+    let mut recorded = collect(&get_conversation(state, session_id, true).await, None);
+    for agent in get_subagent_map(state, session_id).await {
+        let messages = get_subagent_conversation(state, session_id, &agent.agent_id).await;
+        recorded.extend(collect(&messages, Some(&agent.agent_id)));
+    }
+    // Subagents run in between the parent's calls: order everything by time
+    recorded.sort_by(|a, b| a.change.timestamp.cmp(&b.change.timestamp));
+
+    let mut by_file: Vec<(String, Vec<Recorded>)> = Vec::new();
+    for r in recorded {
+        match by_file.iter_mut().find(|(path, _)| *path == r.path) {
+            Some((_, list)) => list.push(r),
+            None => by_file.push((r.path.clone(), vec![r])),
+        }
+    }
+
+    let project = get_session_project(state, session_id).await;
+    let files: Vec<FileChanges> = by_file
+        .into_iter()
+        .map(|(path, recorded)| replay(path, recorded, project.as_deref()))
+        .collect();
+    let diff = files.iter().map(|f| f.diff.as_str()).collect();
+
+    SessionChanges {
+        session_id: session_id.to_string(),
+        files,
+        diff,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(old: &str, new: &str, replace_all: bool) -> EditInput {
+        EditInput {
+            old_string: old.to_string(),
+            new_string: new.to_string(),
+            replace_all,
+        }
+    }
+
+    fn recorded(op: Op, original: Option<&str>, created: bool) -> Recorded {
+        Recorded {
+            path: "/src/app/notes.md".to_string(),
+            op,
+            change: FileChange {
+                tool_use_id: "toolu_1".to_string(),
+                tool: "Edit".to_string(),
+                agent_id: None,
+                message_uuid: None,
+                timestamp: None,
+                diff: String::new(),
+            },
+            original: original.map(String::from),
+            created,
+        }
+    }
+
+    #[test]
+    fn hunk_counts_reads_header_ranges() {
+        assert_eq!(hunk_counts("@@ -1,3 +1,4 @@"), Some((3, 4)));
+        assert_eq!(hunk_counts("@@ -5 +5,0 @@ fn main()"), Some((1, 0)));
+        assert_eq!(hunk_counts("@@ -0,0 +1 @@"), Some((0, 1)));
+        assert_eq!(hunk_counts("--- a/file"), None);
+        assert_eq!(hunk_counts("@@ -1,x +1 @@"), None);
+    }
+
+    #[test]
+    fn diff_stats_counts_dash_and_plus_lines_inside_hunks() {
+        let old = "a\n-- comment\n++ counter\nb\n";
+        let new = "a\n--- rule\n+++ banner\nb\n";
+        let diff = unified_diff(old, new, "a/x", "b/x");
+        assert!(diff.contains("\n--- comment\n") && diff.contains("\n++++ banner\n"));
+        assert_eq!(diff_stats(&diff), (2, 2));
+    }
+
+    #[test]
+    fn diff_stats_sums_concatenated_diffs() {
+        let diff = unified_diff("a\n", "b\n", "a/x", "b/x") + &unified_diff("", "c\nd\n", "/dev/null", "b/y");
+        assert_eq!(diff_stats(&diff), (3, 1));
+    }
+
+    #[test]
+    fn apply_edits_replaces_first_or_all_occurrences() {
+        assert_eq!(apply_edits("x x x", &[edit("x", "y", false)]).as_deref(), Some("y x x"));
+        assert_eq!(apply_edits("x x x", &[edit("x", "y", true)]).as_deref(), Some("y y y"));
+        assert_eq!(apply_edits("", &[edit("", "new", false)]).as_deref(), Some("new"));
+        assert_eq!(apply_edits("x", &[edit("z", "y", false)]), None);
+    }
+
+    #[test]
+    fn replay_replace_all_edit() {
+        let op = Op::Edit(vec![edit("foo", "bar", true)]);
+        let file = replay("/src/app/notes.md".to_string(), vec![recorded(op, Some("foo\nfoo\nbaz\n"), false)], Some("/src/app"));
+        assert!(file.complete);
+        assert!(file.diff.starts_with("--- a/notes.md\n+++ b/notes.md\n"));
+        assert_eq!((file.additions, file.deletions), (2, 2));
+    }
+
+    #[test]
+    fn replay_missing_old_string_is_incomplete() {
+        let first = Op::Edit(vec![edit("one", "two", false)]);
+        let second = Op::Edit(vec![edit("gone", "here", false)]);
+        let file = replay(
+            "/src/app/notes.md".to_string(),
+            vec![recorded(first, Some("one\n"), false), recorded(second, None, false)],
+            Some("/src/app"),
+        );
+        assert!(!file.complete);
+        // Falls back to the per-change diffs, the second one of the snippet alone
+        assert_eq!(file.diff, format!("{}{}", file.changes[0].diff, file.changes[1].diff));
+        assert!(file.changes[1].diff.contains("\n-gone\n") && file.changes[1].diff.contains("\n+here\n"));
+        assert_eq!((file.additions, file.deletions), (2, 2));
+    }
+
+    #[test]
+    fn replay_created_file_diffs_against_dev_null() {
+        let write = Op::Write("hello\nworld\n".to_string());
+        let fix = Op::Edit(vec![edit("world", "there", false)]);
+        let file = replay(
+            "/src/app/notes.md".to_string(),
+            vec![recorded(write, None, true), recorded(fix, None, false)],
+            Some("/src/app"),
+        );
+        assert!(file.created && file.complete);
+        assert!(file.diff.starts_with("--- /dev/null\n+++ b/notes.md\n@@ -0,0 +1,2 @@\n"));
+        assert_eq!((file.additions, file.deletions), (2, 0));
+    }
+}
@@ -0,0 +1,197 @@
+use std::io::{Read, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+const ZSTD_EXT: &str = ".zst";
+const GZIP_EXT: &str = ".gz";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Zstd,
+    Gzip,
+}
+
+impl Codec {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "zstd" | "zst" => Some(Self::Zstd),
+            "gzip" | "gz" => Some(Self::Gzip),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Zstd => ZSTD_EXT,
+            Self::Gzip => GZIP_EXT,
+        }
+    }
+
+    fn of_path(path: &str) -> Option<Self> {
+        if path.ends_with(ZSTD_EXT) {
+            Some(Self::Zstd)
+        } else if path.ends_with(GZIP_EXT) {
+            Some(Self::Gzip)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn is_compressed(path: &str) -> bool {
+    Codec::of_path(path).is_some()
+}
+
+/// Session id of a transcript file name (`<id>.jsonl`, `<id>.jsonl.zst` or `<id>.jsonl.gz`).
+pub fn session_id_from_file_name(name: &str) -> Option<&str> {
+    let name = name
+        .strip_suffix(ZSTD_EXT)
+        .or_else(|| name.strip_suffix(GZIP_EXT))
+        .unwrap_or(name);
+    name.strip_suffix(".jsonl").filter(|id| !id.is_empty())
+}
+
+/// The transcript path without `.jsonl` and any compression extension — the
+/// directory holding the session's `subagents/`.
+pub fn session_dir(file_path: &str) -> &str {
+    let path = file_path
+        .strip_suffix(ZSTD_EXT)
+        .or_else(|| file_path.strip_suffix(GZIP_EXT))
+        .unwrap_or(file_path);
+    path.strip_suffix(".jsonl").unwrap_or(path)
+}
+
+fn decode(codec: Codec, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match codec {
+        Codec::Zstd => {
+            zstd::stream::read::Decoder::new(data)?.read_to_end(&mut out)?;
+        }
+        Codec::Gzip => {
+            flate2::read::GzDecoder::new(data).read_to_end(&mut out)?;
+        }
+    }
+    Ok(out)
+}
+
+fn encode(codec: Codec, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    match codec {
+        Codec::Zstd => zstd::stream::encode_all(data, 0),
+        Codec::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+    }
+}
+
+/// Read a whole transcript, decompressing `.zst` / `.gz` files.
+pub async fn read_transcript(path: &str) -> std::io::Result<Vec<u8>> {
+    let data = fs::read(path).await?;
+    match Codec::of_path(path) {
+        Some(codec) => tokio::task::spawn_blocking(move || decode(codec, &data))
+            .await
+            .map_err(std::io::Error::other)?,
+        None => Ok(data),
+    }
+}
+
+/// An open transcript. Compressed files are decompressed into memory so
+/// offsets always refer to the uncompressed JSONL.
+pub enum TranscriptFile {
+    Plain(fs::File),
+    Decompressed(std::io::Cursor<Vec<u8>>),
+}
+
+impl TranscriptFile {
+    pub async fn open(path: &str) -> std::io::Result<Self> {
+        if is_compressed(path) {
+            Ok(Self::Decompressed(std::io::Cursor::new(read_transcript(path).await?)))
+        } else {
+            Ok(Self::Plain(fs::File::open(path).await?))
+        }
+    }
+
+    /// Length of the uncompressed content.
+    pub async fn len(&self) -> std::io::Result<u64> {
+        match self {
+            Self::Plain(file) => Ok(file.metadata().await?.len()),
+            Self::Decompressed(cursor) => Ok(cursor.get_ref().len() as u64),
+        }
+    }
+}
+
+impl AsyncRead for TranscriptFile {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(file) => Pin::new(file).poll_read(cx, buf),
+            Self::Decompressed(cursor) => Pin::new(cursor).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncSeek for TranscriptFile {
+    fn start_seek(self: Pin<&mut Self>, position: std::io::SeekFrom) -> std::io::Result<()> {
+        match self.get_mut() {
+            Self::Plain(file) => Pin::new(file).start_seek(position),
+            Self::Decompressed(cursor) => Pin::new(cursor).start_seek(position),
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        match self.get_mut() {
+            Self::Plain(file) => Pin::new(file).poll_complete(cx),
+            Self::Decompressed(cursor) => Pin::new(cursor).poll_complete(cx),
+        }
+    }
+}
+
+async fn unchanged(path: &str, before: &std::fs::Metadata) -> std::io::Result<bool> {
+    let after = fs::metadata(path).await?;
+    Ok(after.len() == before.len() && after.modified()? == before.modified()?)
+}
+
+/// Replace `src` with `dst` holding `data`, keeping `src`'s mtime so the
+/// session's last activity doesn't change. Written to a temp file first, and
+/// given up if `src` no longer matches `before` (e.g. lines were appended).
+async fn replace_file(src: &str, dst: &str, data: Vec<u8>, before: &std::fs::Metadata) -> std::io::Result<()> {
+    let tmp_path = format!("{}.tmp", dst);
+    fs::write(&tmp_path, data).await?;
+    let file = std::fs::File::options().write(true).open(&tmp_path)?;
+    file.set_modified(before.modified()?)?;
+    drop(file);
+    if !unchanged(src, before).await? {
+        let _ = fs::remove_file(&tmp_path).await;
+        return Err(std::io::Error::other("file changed while rewriting"));
+    }
+    fs::rename(&tmp_path, dst).await?;
+    fs::remove_file(src).await
+}
+
+/// Compress a `.jsonl` transcript in place. Returns the new path. Gives up if
+/// the file changed while it was being compressed.
+pub async fn compress_file(path: &str, codec: Codec) -> std::io::Result<String> {
+    let before = fs::metadata(path).await?;
+    let data = fs::read(path).await?;
+    let encoded = tokio::task::spawn_blocking(move || encode(codec, &data))
+        .await
+        .map_err(std::io::Error::other)??;
+
+    let new_path = format!("{}{}", path, codec.extension());
+    replace_file(path, &new_path, encoded, &before).await?;
+    Ok(new_path)
+}
+
+/// Decompress a `.jsonl.zst` / `.jsonl.gz` transcript back to `.jsonl`.
+/// Returns the new path.
+pub async fn decompress_file(path: &str) -> std::io::Result<String> {
+    let codec = Codec::of_path(path).ok_or_else(|| std::io::Error::other("not a compressed file"))?;
+    let before = fs::metadata(path).await?;
+    let data = read_transcript(path).await?;
+    let new_path = path.trim_end_matches(codec.extension()).to_string();
+    replace_file(path, &new_path, data, &before).await?;
+    Ok(new_path)
+}
@@ -0,0 +1,190 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::compression::read_transcript;
+use crate::models::{ConversationMessage, ConversationTree, TreeNode};
+use crate::state::AppState;
+use crate::storage::{extract_message_text, find_session_file};
+
+const PREVIEW_LEN: usize = 100;
+
+/// Parent link of one JSONL line. Progress/system lines carry uuids too, so
+/// every line takes part in the chain even if it is never displayed.
+struct Link {
+    parent: Option<String>,
+    displayed: bool,
+    sidechain: bool,
+}
+
+fn is_sidechain(msg: &ConversationMessage) -> bool {
+    msg.extra
+        .get("isSidechain")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Parent of a line. Compaction boundaries start a new chain and point back
+/// through `logicalParentUuid` instead.
+fn parent_of(msg: &ConversationMessage) -> Option<String> {
+    msg.parent_uuid.clone().or_else(|| {
+        msg.extra
+            .get("logicalParentUuid")
+            .and_then(|v| v.as_str())
+            .map(String::from)
+    })
+}
+
+fn parse_lines(content: &[u8]) -> Vec<(u64, ConversationMessage)> {
+    let mut lines = Vec::new();
+    let mut offset: u64 = 0;
+    for line in content.split(|&b| b == b'\n') {
+        let line_offset = offset;
+        offset += line.len() as u64 + 1;
+        if line.is_empty() {
+            continue;
+        }
+        if let Ok(msg) = serde_json::from_slice::<ConversationMessage>(line) {
+            lines.push((line_offset, msg));
+        }
+    }
+    lines
+}
+
+fn build_links(lines: &[(u64, ConversationMessage)]) -> HashMap<String, Link> {
+    lines
+        .iter()
+        .filter_map(|(_, msg)| {
+            let uuid = msg.uuid.clone()?;
+            Some((
+                uuid,
+                Link {
+                    parent: parent_of(msg),
+                    displayed: msg.msg_type == "user" || msg.msg_type == "assistant",
+                    sidechain: is_sidechain(msg),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Nearest displayed ancestor of `uuid`, skipping progress/system lines.
+fn displayed_parent(links: &HashMap<String, Link>, uuid: &str) -> Option<String> {
+    let mut seen = HashSet::new();
+    let mut current = links.get(uuid)?.parent.clone();
+    while let Some(id) = current {
+        if !seen.insert(id.clone()) {
+            return None;
+        }
+        match links.get(&id) {
+            Some(link) if link.displayed => return Some(id),
+            Some(link) => current = link.parent.clone(),
+            None => return None,
+        }
+    }
+    None
+}
+
+/// The leaf Claude resumes from: the last main-chain message in file order.
+fn active_leaf(lines: &[(u64, ConversationMessage)], links: &HashMap<String, Link>) -> Option<String> {
+    lines
+        .iter()
+        .rev()
+        .filter_map(|(_, msg)| msg.uuid.as_ref())
+        .find(|uuid| links.get(*uuid).is_some_and(|l| l.displayed && !l.sidechain))
+        .cloned()
+}
+
+fn ancestry(links: &HashMap<String, Link>, leaf: &str) -> HashSet<String> {
+    let mut path = HashSet::new();
+    let mut current = Some(leaf.to_string());
+    while let Some(id) = current {
+        if !path.insert(id.clone()) {
+            break;
+        }
+        current = links.get(&id).and_then(|l| l.parent.clone());
+    }
+    path
+}
+
+/// Uuids on the path from the root to the active leaf. `None` when the
+/// transcript has no linked messages (nothing to filter).
+pub fn active_branch(content: &[u8]) -> Option<HashSet<String>> {
+    let lines = parse_lines(content);
+    let links = build_links(&lines);
+    let leaf = active_leaf(&lines, &links)?;
+    Some(ancestry(&links, &leaf))
+}
+
+/// The active branch of a session's transcript as it is on disk now.
+pub async fn get_active_branch(state: &AppState, session_id: &str) -> Option<HashSet<String>> {
+    let file_path = find_session_file(state, session_id).await?;
+    let content = read_transcript(&file_path).await.ok()?;
+    active_branch(&content)
+}
+
+/// Whether a message belongs on the active branch. Lines without a uuid
+/// (summaries, queued notifications) are always kept.
+pub fn on_branch(branch: &Option<HashSet<String>>, msg: &ConversationMessage) -> bool {
+    match (branch, &msg.uuid) {
+        (Some(branch), Some(uuid)) => branch.contains(uuid),
+        _ => true,
+    }
+}
+
+pub async fn get_conversation_tree(state: &AppState, session_id: &str) -> Option<ConversationTree> {
+    let file_path = find_session_file(state, session_id).await?;
+    let content = read_transcript(&file_path).await.ok()?;
+
+    let lines = parse_lines(&content);
+    let links = build_links(&lines);
+    let active_leaf = active_leaf(&lines, &links);
+    let active = active_leaf
+        .as_deref()
+        .map(|leaf| ancestry(&links, leaf))
+        .unwrap_or_default();
+
+    let mut nodes: Vec<TreeNode> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (offset, msg) in &lines {
+        let uuid = match &msg.uuid {
+            Some(u) if msg.msg_type == "user" || msg.msg_type == "assistant" => u.clone(),
+            _ => continue,
+        };
+        if positions.contains_key(&uuid) {
+            continue;
+        }
+        let preview: String = extract_message_text(msg).chars().take(PREVIEW_LEN).collect();
+        positions.insert(uuid.clone(), nodes.len());
+        nodes.push(TreeNode {
+            parent_uuid: displayed_parent(&links, &uuid),
+            msg_type: msg.msg_type.clone(),
+            timestamp: msg.timestamp.clone(),
+            is_sidechain: is_sidechain(msg),
+            on_active_branch: active.contains(&uuid),
+            children: Vec::new(),
+            preview,
+            offset: *offset,
+            uuid,
+        });
+    }
+
+    let mut roots = Vec::new();
+    for i in 0..nodes.len() {
+        let child = nodes[i].uuid.clone();
+        match nodes[i].parent_uuid.as_ref().and_then(|p| positions.get(p)).copied() {
+            Some(parent) => nodes[parent].children.push(child),
+            None => roots.push(child),
+        }
+    }
+    let branch_points = nodes
+        .iter()
+        .filter(|n| n.children.len() > 1)
+        .map(|n| n.uuid.clone())
+        .collect();
+
+    Some(ConversationTree {
+        nodes,
+        roots,
+        branch_points,
+        active_leaf,
+    })
+}
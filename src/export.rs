@@ -0,0 +1,777 @@
+use std::collections::{HashMap, HashSet};
+
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+
+use crate::models::{ContentBlock, ContentBlockContent, ConversationMessage, MessageContent};
+use crate::session_meta::get_session_meta;
+use crate::state::AppState;
+use crate::storage::{
+    extract_message_text, find_session_file, get_conversation, get_plan_session_map, get_project_name,
+    get_session_project, get_sessions, get_subagent_conversation, get_subagent_map,
+};
+use crate::user_meta;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" => Some(Self::Html),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Markdown => "text/markdown; charset=utf-8",
+            Self::Html => "text/html; charset=utf-8",
+            Self::Json => "application/json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+            Self::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExportOptions {
+    pub thinking: bool,
+    pub tool_results: bool,
+    /// Inline subagent transcripts under the Task call that spawned them
+    pub subagents: bool,
+}
+
+enum Block {
+    Text(String),
+    Thinking(String),
+    /// ExitPlanMode plan and the session that implemented it
+    Plan {
+        plan: String,
+        session: Option<String>,
+    },
+    ToolUse {
+        name: String,
+        input: serde_json::Value,
+        subagent: Option<Vec<Entry>>,
+    },
+    ToolResult {
+        text: String,
+        is_error: bool,
+    },
+}
+
+struct Entry {
+    role: String,
+    timestamp: Option<String>,
+    blocks: Vec<Block>,
+}
+
+/// A conversation prepared for rendering.
+pub struct ExportDoc {
+    pub session_id: String,
+    pub title: String,
+    pub project: Option<String>,
+    summaries: Vec<String>,
+    entries: Vec<Entry>,
+    /// Raw material for the JSON format
+    messages: Vec<ConversationMessage>,
+    subagent_messages: HashMap<String, Vec<ConversationMessage>>,
+    plan_sessions: HashMap<String, String>,
+}
+
+fn nested_text(content: &ContentBlockContent) -> String {
+    match content {
+        ContentBlockContent::Text(s) => s.clone(),
+        ContentBlockContent::Blocks(blocks) => blocks
+            .iter()
+            .map(|b| match (&b.text, b.block_type.as_str()) {
+                (Some(t), _) => t.clone(),
+                (None, "image") => "[image]".to_string(),
+                _ => String::new(),
+            })
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn build_entries(
+    messages: &[ConversationMessage],
+    options: ExportOptions,
+    subagents: &mut HashMap<String, Vec<Entry>>,
+    plan_sessions: &HashMap<String, String>,
+) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for msg in messages {
+        if msg.msg_type != "user" && msg.msg_type != "assistant" {
+            continue;
+        }
+        let content = match msg.message.as_ref().and_then(|m| m.content.as_ref()) {
+            Some(c) => c,
+            None => continue,
+        };
+        let mut blocks = Vec::new();
+        match content {
+            MessageContent::Text(text) => {
+                if !text.trim().is_empty() {
+                    blocks.push(Block::Text(text.clone()));
+                }
+            }
+            MessageContent::Blocks(content_blocks) => {
+                for block in content_blocks {
+                    if let Some(b) = convert_block(block, options, subagents, plan_sessions) {
+                        blocks.push(b);
+                    }
+                }
+            }
+        }
+        if !blocks.is_empty() {
+            entries.push(Entry {
+                role: msg.msg_type.clone(),
+                timestamp: msg.timestamp.clone(),
+                blocks,
+            });
+        }
+    }
+    entries
+}
+
+fn convert_block(
+    block: &ContentBlock,
+    options: ExportOptions,
+    subagents: &mut HashMap<String, Vec<Entry>>,
+    plan_sessions: &HashMap<String, String>,
+) -> Option<Block> {
+    match block.block_type.as_str() {
+        "text" => block
+            .text
+            .clone()
+            .filter(|t| !t.trim().is_empty())
+            .map(Block::Text),
+        "thinking" if options.thinking => block
+            .thinking
+            .clone()
+            .filter(|t| !t.trim().is_empty())
+            .map(Block::Thinking),
+        "image" => Some(Block::Text("*[image]*".to_string())),
+        "tool_use" => {
+            let name = block.name.clone().unwrap_or_default();
+            let input = block.input.clone().unwrap_or(serde_json::Value::Null);
+            let id = block.id.as_deref().unwrap_or("");
+            if name == "ExitPlanMode" {
+                if let Some(plan) = input.get("plan").and_then(|p| p.as_str()) {
+                    return Some(Block::Plan {
+                        plan: plan.to_string(),
+                        session: plan_sessions.get(id).cloned(),
+                    });
+                }
+            }
+            Some(Block::ToolUse {
+                name,
+                input,
+                subagent: subagents.remove(id),
+            })
+        }
+        "tool_result" if options.tool_results => Some(Block::ToolResult {
+            text: block.content.as_ref().map(nested_text).unwrap_or_default(),
+            is_error: block.is_error.unwrap_or(false),
+        }),
+        _ => None,
+    }
+}
+
+/// Drop the blocks the options exclude (for the JSON format).
+fn filter_message(mut msg: ConversationMessage, options: ExportOptions) -> ConversationMessage {
+    if let Some(MessageContent::Blocks(ref mut blocks)) = msg.message.as_mut().and_then(|m| m.content.as_mut()) {
+        blocks.retain(|b| match b.block_type.as_str() {
+            "thinking" => options.thinking,
+            "tool_result" => options.tool_results,
+            _ => true,
+        });
+    }
+    msg
+}
+
+/// Collect a session's conversation, subagents and plan links for export.
+pub async fn build_export(state: &AppState, session_id: &str, options: ExportOptions) -> Option<ExportDoc> {
+    find_session_file(state, session_id).await?;
+
+    let messages = get_conversation(state, session_id, false).await;
+    let meta = get_session_meta(state, session_id).await;
+    let title = user_meta::get_user_meta(state, session_id)
+        .title
+        .or_else(|| state.summary_cache.get(session_id).map(|s| s.0.clone()))
+        .or_else(|| meta.and_then(|m| m.first_prompt))
+        .unwrap_or_else(|| session_id.to_string());
+    let project = get_session_project(state, session_id).await;
+
+    let plan_sessions: HashMap<String, String> = get_plan_session_map(state, session_id)
+        .await
+        .into_iter()
+        .map(|p| (p.tool_use_id, p.session_id))
+        .collect();
+
+    let mut subagents = HashMap::new();
+    let mut subagent_messages = HashMap::new();
+    if options.subagents {
+        for info in get_subagent_map(state, session_id).await {
+            let sub_messages = get_subagent_conversation(state, session_id, &info.agent_id).await;
+            let entries = build_entries(&sub_messages, options, &mut HashMap::new(), &HashMap::new());
+            subagents.insert(info.tool_use_id, entries);
+            subagent_messages.insert(
+                info.agent_id,
+                sub_messages.into_iter().map(|m| filter_message(m, options)).collect(),
+            );
+        }
+    }
+
+    let summaries = messages
+        .iter()
+        .filter(|m| m.msg_type == "summary")
+        .filter_map(|m| m.summary.clone())
+        .collect();
+    let entries = build_entries(&messages, options, &mut subagents, &plan_sessions);
+
+    Some(ExportDoc {
+        session_id: session_id.to_string(),
+        title,
+        project,
+        summaries,
+        entries,
+        messages: messages.into_iter().map(|m| filter_message(m, options)).collect(),
+        subagent_messages,
+        plan_sessions,
+    })
+}
+
+pub fn render(doc: &ExportDoc, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Markdown => render_markdown(doc),
+        ExportFormat::Html => render_html(doc),
+        ExportFormat::Json => render_json(doc),
+    }
+}
+
+/// Short description of a tool call for headings.
+fn tool_brief(input: &serde_json::Value) -> Option<String> {
+    let brief = ["command", "file_path", "pattern", "url", "description", "query"]
+        .iter()
+        .find_map(|key| input.get(*key).and_then(|v| v.as_str()))?;
+    let line = brief.lines().next().unwrap_or("");
+    Some(line.chars().take(100).collect())
+}
+
+fn role_label(role: &str) -> &'static str {
+    if role == "user" {
+        "👤 User"
+    } else {
+        "🤖 Claude"
+    }
+}
+
+// --- Markdown ---
+
+/// A code fence longer than any backtick run in `text`.
+fn fence(text: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        if c == '`' {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    "`".repeat((longest + 1).max(3))
+}
+
+fn fenced(text: &str, lang: &str) -> String {
+    let f = fence(text);
+    format!("{}{}\n{}\n{}\n\n", f, lang, text.trim_end(), f)
+}
+
+fn quote(text: &str) -> String {
+    text.lines()
+        .map(|l| if l.is_empty() { ">".to_string() } else { format!("> {}", l) })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n\n"
+}
+
+fn markdown_entries(entries: &[Entry], heading: &str) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        out += &format!("{} {}\n\n", heading, role_label(&entry.role));
+        if let Some(ref ts) = entry.timestamp {
+            out += &format!("*{}*\n\n", ts);
+        }
+        for block in &entry.blocks {
+            match block {
+                Block::Text(text) => out += &format!("{}\n\n", text.trim_end()),
+                Block::Thinking(text) => out += &quote(&format!("💭 *Thinking*\n\n{}", text.trim_end())),
+                Block::Plan { plan, session } => {
+                    out += &format!("**📋 Plan**\n\n{}\n\n", plan.trim_end());
+                    if let Some(session) = session {
+                        out += &format!("*Implemented in session `{}`*\n\n", session);
+                    }
+                }
+                Block::ToolUse { name, input, subagent } => {
+                    match tool_brief(input) {
+                        Some(brief) => out += &format!("**🔧 {}** `{}`\n\n", name, brief.replace('`', "'")),
+                        None => out += &format!("**🔧 {}**\n\n", name),
+                    }
+                    let json = serde_json::to_string_pretty(input).unwrap_or_default();
+                    out += &fenced(&json, "json");
+                    if let Some(sub) = subagent {
+                        out += &quote(&format!("**Subagent transcript**\n\n{}", markdown_entries(sub, "####").trim_end()));
+                    }
+                }
+                Block::ToolResult { text, is_error } => {
+                    out += if *is_error { "**Result (error)**\n\n" } else { "**Result**\n\n" };
+                    out += &fenced(text, "");
+                }
+            }
+        }
+    }
+    out
+}
+
+fn render_markdown(doc: &ExportDoc) -> String {
+    let mut out = format!("# {}\n\n", doc.title.lines().next().unwrap_or(""));
+    if let Some(ref project) = doc.project {
+        out += &format!("**Project**: {}  \n", project);
+    }
+    out += &format!("**Session**: `{}`\n\n---\n\n", doc.session_id);
+    for summary in &doc.summaries {
+        out += &quote(&format!("📋 **Summary**: {}", summary));
+    }
+    out += &markdown_entries(&doc.entries, "##");
+    out += "---\n\n*Exported from Claude Run*\n";
+    out
+}
+
+// --- HTML ---
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Whether a link or image URL is kept: http(s), mailto, relative and `#` links.
+fn safe_url(url: &str) -> bool {
+    // Browsers ignore whitespace and control characters inside the scheme
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_lowercase();
+    match url.find([':', '/', '?', '#']) {
+        Some(i) if url[i..].starts_with(':') => matches!(&url[..i], "http" | "https" | "mailto"),
+        _ => true,
+    }
+}
+
+/// Render markdown to HTML. Raw HTML in the source is shown as text and links
+/// or images with other URL schemes (e.g. `javascript:`) keep only their text,
+/// so a transcript cannot inject markup or script into the exported page.
+fn markdown_html(text: &str) -> String {
+    let parser = Parser::new_ext(text, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS)
+        .filter_map(|event| match event {
+            Event::Html(raw) => Some(Event::Text(raw)),
+            Event::Start(Tag::Link(_, ref url, _) | Tag::Image(_, ref url, _))
+            | Event::End(Tag::Link(_, ref url, _) | Tag::Image(_, ref url, _))
+                if !safe_url(url) =>
+            {
+                None
+            }
+            other => Some(other),
+        });
+    let mut out = String::new();
+    html::push_html(&mut out, parser);
+    out
+}
+
+const HTML_STYLE: &str = r#"
+:root { color-scheme: light dark; --bg: #fafafa; --fg: #1f2328; --muted: #6e7781; --card: #fff; --border: #d0d7de; --accent: #d97757; --code: #f3f4f6; --error: #cf222e; }
+@media (prefers-color-scheme: dark) { :root { --bg: #0d1117; --fg: #e6edf3; --muted: #8b949e; --card: #161b22; --border: #30363d; --code: #1f2430; --error: #f85149; } }
+* { box-sizing: border-box; }
+body { margin: 0; background: var(--bg); color: var(--fg); font: 15px/1.6 -apple-system, BlinkMacSystemFont, "Segoe UI", Helvetica, Arial, sans-serif; }
+main { max-width: 860px; margin: 0 auto; padding: 24px 16px 64px; }
+h1 { font-size: 1.5em; margin: 0 0 4px; }
+.meta { color: var(--muted); font-size: 0.85em; margin: 0 0 24px; }
+.summary { border-left: 3px solid var(--accent); padding: 4px 12px; margin: 0 0 24px; color: var(--muted); }
+.msg { background: var(--card); border: 1px solid var(--border); border-radius: 8px; padding: 12px 16px; margin: 0 0 12px; }
+.msg.user { border-left: 3px solid var(--accent); }
+.role { font-weight: 600; font-size: 0.85em; color: var(--muted); margin-bottom: 4px; }
+.role time { font-weight: normal; margin-left: 8px; }
+pre, code { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; font-size: 0.85em; }
+pre { background: var(--code); padding: 10px 12px; border-radius: 6px; overflow-x: auto; white-space: pre-wrap; word-break: break-word; }
+:not(pre) > code { background: var(--code); padding: 1px 4px; border-radius: 4px; }
+details { margin: 8px 0; border: 1px solid var(--border); border-radius: 6px; padding: 4px 10px; }
+summary { cursor: pointer; color: var(--muted); font-size: 0.9em; }
+details.error summary { color: var(--error); }
+.thinking { font-style: italic; }
+.plan { border: 1px solid var(--accent); border-radius: 6px; padding: 8px 12px; margin: 8px 0; }
+.label { font-weight: 600; font-size: 0.85em; color: var(--accent); }
+.subagent { border-left: 2px solid var(--border); padding-left: 12px; margin-top: 8px; }
+.subagent .msg { background: transparent; }
+#search { width: 100%; padding: 8px 12px; font-size: 1em; border: 1px solid var(--border); border-radius: 6px; background: var(--card); color: var(--fg); margin-bottom: 16px; }
+.project h2 { font-size: 1.15em; margin: 24px 0 0; }
+.row { padding: 8px 0; border-bottom: 1px solid var(--border); }
+a { color: var(--accent); }
+footer { color: var(--muted); font-size: 0.8em; margin-top: 32px; text-align: center; }
+table { border-collapse: collapse; } td, th { border: 1px solid var(--border); padding: 4px 8px; }
+"#;
+
+/// `site` holds the sessions exported alongside this page, when rendering a site.
+fn html_entries(entries: &[Entry], site: Option<&HashSet<String>>) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        let role = if entry.role == "user" { "user" } else { "assistant" };
+        out += &format!("<article class=\"msg {}\"><div class=\"role\">{}", role, role_label(role));
+        if let Some(ref ts) = entry.timestamp {
+            out += &format!("<time>{}</time>", escape_html(ts));
+        }
+        out += "</div>";
+        for block in &entry.blocks {
+            match block {
+                Block::Text(text) => out += &markdown_html(text),
+                Block::Thinking(text) => {
+                    out += &format!(
+                        "<details class=\"thinking\"><summary>💭 Thinking</summary>{}</details>",
+                        markdown_html(text)
+                    )
+                }
+                Block::Plan { plan, session } => {
+                    out += &format!("<div class=\"plan\"><div class=\"label\">📋 Plan</div>{}", markdown_html(plan));
+                    if let Some(session) = session {
+                        let linked = site.is_some_and(|pages| pages.contains(session));
+                        let session = escape_html(session);
+                        if linked {
+                            out += &format!(
+                                "<p class=\"meta\">Implemented in session <a href=\"{0}.html\"><code>{0}</code></a></p>",
+                                session
+                            );
+                        } else {
+                            out += &format!("<p class=\"meta\">Implemented in session <code>{}</code></p>", session);
+                        }
+                    }
+                    out += "</div>";
+                }
+                Block::ToolUse { name, input, subagent } => {
+                    let brief = tool_brief(input)
+                        .map(|b| format!(" <code>{}</code>", escape_html(&b)))
+                        .unwrap_or_default();
+                    let json = serde_json::to_string_pretty(input).unwrap_or_default();
+                    out += &format!(
+                        "<details class=\"tool\"><summary>🔧 {}{}</summary><pre>{}</pre>",
+                        escape_html(name),
+                        brief,
+                        escape_html(&json)
+                    );
+                    if let Some(sub) = subagent {
+                        out += &format!("<div class=\"subagent\">{}</div>", html_entries(sub, site));
+                    }
+                    out += "</details>";
+                }
+                Block::ToolResult { text, is_error } => {
+                    out += &format!(
+                        "<details class=\"result{}\"><summary>{}</summary><pre>{}</pre></details>",
+                        if *is_error { " error" } else { "" },
+                        if *is_error { "Result (error)" } else { "Result" },
+                        escape_html(text)
+                    );
+                }
+            }
+        }
+        out += "</article>\n";
+    }
+    out
+}
+
+fn html_page(title: &str, body: &str, script: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<main>\n{}\n</main>\n{}</body>\n</html>\n",
+        title, HTML_STYLE, body, script
+    )
+}
+
+/// Page body for one session. In a site, plan links point at sibling pages
+/// for the sessions in `site`.
+fn session_body(doc: &ExportDoc, site: Option<&HashSet<String>>) -> String {
+    let title = escape_html(doc.title.lines().next().unwrap_or(""));
+    let mut body = String::new();
+    if site.is_some() {
+        body += "<nav class=\"meta\"><a href=\"../index.html\">← All sessions</a></nav>\n";
+    }
+    body += &format!("<header><h1>{}</h1><p class=\"meta\">", title);
+    if let Some(ref project) = doc.project {
+        body += &format!("{} · ", escape_html(project));
+    }
+    body += &format!("<code>{}</code></p></header>\n", escape_html(&doc.session_id));
+    for summary in &doc.summaries {
+        body += &format!("<div class=\"summary\">📋 {}</div>\n", escape_html(summary));
+    }
+    body += &html_entries(&doc.entries, site);
+    body += "<footer>Exported from Claude Run</footer>";
+    body
+}
+
+/// A single self-contained page: inline CSS, no scripts or external assets.
+fn render_html(doc: &ExportDoc) -> String {
+    let title = escape_html(doc.title.lines().next().unwrap_or(""));
+    html_page(&title, &session_body(doc, None), "")
+}
+
+// --- JSON ---
+
+fn render_json(doc: &ExportDoc) -> String {
+    let data = serde_json::json!({
+        "sessionId": doc.session_id,
+        "title": doc.title,
+        "project": doc.project,
+        "messages": doc.messages,
+        "subagents": doc.subagent_messages,
+        "planSessions": doc.plan_sessions,
+    });
+    serde_json::to_string_pretty(&data).unwrap_or_default()
+}
+
+// --- Static site ---
+
+/// Characters of transcript text per session kept in the client-side search index.
+const SITE_SEARCH_TEXT_LEN: usize = 20_000;
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SiteSearchEntry {
+    id: String,
+    title: String,
+    project: String,
+    date: String,
+    summary: Option<String>,
+    text: String,
+}
+
+fn format_date(ms: f64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms as i64)
+        .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+const SITE_SEARCH_SCRIPT: &str = r#"<script src="search-index.js"></script>
+<script>
+(function () {
+  var input = document.getElementById('search');
+  var results = document.getElementById('results');
+  var projects = document.getElementById('projects');
+  function esc(s) { var d = document.createElement('div'); d.textContent = s || ''; return d.innerHTML; }
+  input.addEventListener('input', function () {
+    var terms = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+    if (!terms.length) { results.innerHTML = ''; projects.hidden = false; return; }
+    projects.hidden = true;
+    var hits = (window.SEARCH_INDEX || []).filter(function (s) {
+      var hay = (s.title + ' ' + s.project + ' ' + (s.summary || '') + ' ' + s.text).toLowerCase();
+      return terms.every(function (t) { return hay.indexOf(t) !== -1; });
+    });
+    results.innerHTML = '<p class="meta">' + hits.length + ' result(s)</p>' + hits.map(function (s) {
+      var i = s.text.toLowerCase().indexOf(terms[0]);
+      var snippet = i === -1 ? '' : s.text.slice(Math.max(0, i - 60), i + 100);
+      return '<div class="row"><a href="sessions/' + encodeURIComponent(s.id) + '.html">' + esc(s.title) + '</a>' +
+        '<div class="meta">' + esc(s.project) + ' · ' + esc(s.date) + '</div>' +
+        (snippet ? '<div class="meta">…' + esc(snippet) + '…</div>' : '') + '</div>';
+    }).join('');
+  });
+})();
+</script>
+"#;
+
+fn is_page_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Render every session (optionally only one project) into a static,
+/// read-only site: `index.html` grouped by project, one page per session under
+/// `sessions/`, and `search-index.js` for client-side search. Returns the
+/// number of sessions written.
+pub async fn export_site(
+    state: &AppState,
+    outdir: &str,
+    project: Option<&str>,
+    options: ExportOptions,
+) -> Result<usize, String> {
+    let sessions_dir = format!("{}/sessions", outdir);
+    tokio::fs::create_dir_all(&sessions_dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", sessions_dir, e))?;
+
+    // Ids come from history.jsonl and name the page files, so only uuid-like ids are exported
+    let mut sessions = Vec::new();
+    for session in get_sessions(state, None).await {
+        if project.is_some_and(|p| session.project != p && session.project_name != p)
+            || !is_page_id(&session.id)
+            || find_session_file(state, &session.id).await.is_none()
+        {
+            continue;
+        }
+        sessions.push(session);
+    }
+    let pages: HashSet<String> = sessions.iter().map(|s| s.id.clone()).collect();
+
+    // Projects in order of most recent activity; sessions are already sorted that way
+    let mut groups: Vec<(String, Vec<&crate::models::Session>)> = Vec::new();
+    for session in &sessions {
+        match groups.iter_mut().find(|(p, _)| *p == session.project) {
+            Some((_, list)) => list.push(session),
+            None => groups.push((session.project.clone(), vec![session])),
+        }
+    }
+
+    let mut search_entries = Vec::new();
+    for session in &sessions {
+        let doc = match build_export(state, &session.id, options).await {
+            Some(d) => d,
+            None => continue,
+        };
+        let page = html_page(
+            &escape_html(doc.title.lines().next().unwrap_or("")),
+            &session_body(&doc, Some(&pages)),
+            "",
+        );
+        let page_path = format!("{}/{}.html", sessions_dir, session.id);
+        tokio::fs::write(&page_path, page)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", page_path, e))?;
+
+        let text: String = doc
+            .messages
+            .iter()
+            .filter(|m| m.msg_type == "user" || m.msg_type == "assistant")
+            .map(extract_message_text)
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .chars()
+            .take(SITE_SEARCH_TEXT_LEN)
+            .collect();
+        search_entries.push(SiteSearchEntry {
+            id: session.id.clone(),
+            title: session
+                .title
+                .clone()
+                .or_else(|| session.summary.clone())
+                .unwrap_or_else(|| session.display.clone()),
+            project: session.project.clone(),
+            date: format_date(session.last_activity),
+            summary: session.summary.clone(),
+            text,
+        });
+    }
+
+    let mut body = String::from(
+        "<header><h1>Claude sessions</h1></header>\n<input id=\"search\" type=\"search\" placeholder=\"Search sessions…\" autofocus>\n<div id=\"results\"></div>\n<div id=\"projects\">\n",
+    );
+    for (project, list) in &groups {
+        body += &format!(
+            "<section class=\"project\"><h2>{}</h2><p class=\"meta\">{}</p>\n",
+            escape_html(&get_project_name(project)),
+            escape_html(project)
+        );
+        for session in list {
+            if !search_entries.iter().any(|e| e.id == session.id) {
+                continue;
+            }
+            body += &format!(
+                "<div class=\"row\"><a href=\"sessions/{}.html\">{}</a><div class=\"meta\">{} · {} messages</div>",
+                escape_html(&session.id),
+                escape_html(session.title.as_deref().unwrap_or(&session.display).lines().next().unwrap_or("")),
+                format_date(session.last_activity),
+                session.message_count
+            );
+            if let Some(ref summary) = session.summary {
+                body += &format!("<div class=\"meta\">{}</div>", escape_html(summary));
+            }
+            body += "</div>\n";
+        }
+        body += "</section>\n";
+    }
+    body += "</div>\n<footer>Exported from Claude Run</footer>";
+
+    let index_path = format!("{}/index.html", outdir);
+    tokio::fs::write(&index_path, html_page("Claude sessions", &body, SITE_SEARCH_SCRIPT))
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", index_path, e))?;
+
+    let json = serde_json::to_string(&search_entries).map_err(|e| e.to_string())?;
+    let search_path = format!("{}/search-index.js", outdir);
+    tokio::fs::write(&search_path, format!("window.SEARCH_INDEX = {};\n", json))
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", search_path, e))?;
+
+    Ok(search_entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_url_rejects_script_and_data_schemes() {
+        assert!(!safe_url("javascript:alert(1)"));
+        assert!(!safe_url("JaVaScRiPt:alert(1)"));
+        assert!(!safe_url("java\tscript:alert(1)"));
+        assert!(!safe_url(" javascript:alert(1)"));
+        assert!(!safe_url("data:text/html;base64,PHNjcmlwdD4="));
+        assert!(!safe_url("DATA:image/svg+xml,<svg/>"));
+        assert!(!safe_url("vbscript:msgbox(1)"));
+    }
+
+    #[test]
+    fn safe_url_keeps_web_and_relative_links() {
+        assert!(safe_url("https://example.com/a?b=c"));
+        assert!(safe_url("HTTP://example.com"));
+        assert!(safe_url("mailto:someone@example.com"));
+        assert!(safe_url("docs/readme.md"));
+        assert!(safe_url("./a:b.md"));
+        assert!(safe_url("/path/to:file"));
+        assert!(safe_url("?q=a:b"));
+        assert!(safe_url("#section"));
+        assert!(safe_url(""));
+    }
+
+    #[test]
+    fn markdown_html_drops_unsafe_links_and_raw_html() {
+        let out = markdown_html("[click](javascript:alert(1)) ![img](data:image/png;base64,AA) [ok](https://example.com)");
+        assert!(!out.contains("javascript:"));
+        assert!(!out.contains("data:"));
+        assert!(out.contains("click"));
+        assert!(out.contains(r#"<a href="https://example.com">ok</a>"#));
+
+        let out = markdown_html("<script>alert(1)</script>");
+        assert!(!out.contains("<script>"));
+        assert!(out.contains("&lt;script&gt;"));
+    }
+}
@@ -0,0 +1,431 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use serde::Deserialize;
+use tokio::fs;
+use tokio::task::JoinSet;
+use tokio_stream::StreamExt;
+
+use crate::models::{SearchResult, Session, SessionStatus};
+use crate::state::AppState;
+use crate::storage::sort_sessions;
+
+/// Sent on every request to a peer. Peers answer with their own sessions only,
+/// so two instances listing each other don't echo sessions back and forth.
+pub const PEER_HEADER: &str = "x-claude-run-peer";
+
+const RECONNECT_MIN: Duration = Duration::from_secs(5);
+const RECONNECT_MAX: Duration = Duration::from_secs(60);
+/// Peers send a heartbeat every 30s; a silent stream is considered dead
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const PEER_SEARCH_TIMEOUT: Duration = Duration::from_secs(30);
+const PROXY_BODY_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Another claude-run instance whose sessions are shown here.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Peer {
+    pub label: String,
+    pub url: String,
+    /// Sent as a bearer token; must match the peer's `--token`
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// A session owned by a peer, as last received from its stream.
+#[derive(Debug, Clone)]
+pub struct PeerSession {
+    pub peer: usize,
+    pub session: Session,
+    /// When it last changed (ms since the epoch), so SSE clients resend it
+    pub revision: f64,
+}
+
+fn now_ms() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+/// Load the peer list: a JSON array of `{label, url, token?}`.
+pub async fn load_peers(path: &str) -> Vec<Peer> {
+    let content = match fs::read(path).await {
+        Ok(c) => c,
+        Err(_) => return Vec::new(),
+    };
+    match serde_json::from_slice::<Vec<Peer>>(&content) {
+        Ok(peers) => peers
+            .into_iter()
+            .map(|p| Peer {
+                url: p.url.trim_end_matches('/').to_string(),
+                ..p
+            })
+            .collect(),
+        Err(e) => {
+            eprintln!("[federation] invalid {}: {}", path, e);
+            Vec::new()
+        }
+    }
+}
+
+pub fn is_peer_request(headers: &HeaderMap) -> bool {
+    headers.contains_key(PEER_HEADER)
+}
+
+fn peer_request(client: &reqwest::Client, peer: &Peer, method: reqwest::Method, path_and_query: &str) -> reqwest::RequestBuilder {
+    let request = client
+        .request(method, format!("{}{}", peer.url, path_and_query))
+        .header(PEER_HEADER, "1");
+    match peer.token {
+        Some(ref token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+// --- Session sync ---
+
+/// Follow every peer's `/api/sessions/stream`, reconnecting with backoff.
+pub fn spawn_peer_sync(state: Arc<AppState>) {
+    for index in 0..state.peers.len() {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let label = state.peers[index].label.clone();
+            let mut delay = RECONNECT_MIN;
+            loop {
+                match follow_peer(&state, index).await {
+                    Ok(()) => {
+                        eprintln!("[federation] {} closed the stream", label);
+                        delay = RECONNECT_MIN;
+                    }
+                    Err(e) => eprintln!("[federation] {}: {}", label, e),
+                }
+                if clear_peer(&state, index) {
+                    let _ = state.history_tx.send(());
+                }
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(RECONNECT_MAX);
+            }
+        });
+    }
+}
+
+/// Drop a peer's sessions. Returns whether there were any.
+fn clear_peer(state: &AppState, index: usize) -> bool {
+    let before = state.peer_sessions.len();
+    state.peer_sessions.retain(|_, p| p.peer != index);
+    state.peer_sessions.len() != before
+}
+
+async fn follow_peer(state: &AppState, index: usize) -> Result<(), String> {
+    let peer = &state.peers[index];
+    let client = reqwest::Client::new();
+    let response = peer_request(&client, peer, reqwest::Method::GET, "/api/sessions/stream")
+        .header(header::ACCEPT, "text/event-stream")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("sessions stream returned {}", response.status()));
+    }
+    eprintln!("[federation] connected to {} ({})", peer.label, peer.url);
+
+    let mut body = response.bytes_stream();
+    // Raw bytes: a chunk may end inside a multi-byte character, so only
+    // complete events are decoded
+    let mut buf: Vec<u8> = Vec::new();
+    loop {
+        let chunk = match tokio::time::timeout(STREAM_IDLE_TIMEOUT, body.next()).await {
+            Ok(Some(chunk)) => chunk.map_err(|e| e.to_string())?,
+            Ok(None) => return Ok(()),
+            Err(_) => return Err("stream went silent".to_string()),
+        };
+        buf.extend_from_slice(&chunk);
+        while let Some((end, len)) = event_end(&buf) {
+            let block: Vec<u8> = buf.drain(..end + len).collect();
+            if let Some((event, data)) = parse_event(&String::from_utf8_lossy(&block[..end])) {
+                apply_event(state, index, &event, &data);
+            }
+        }
+    }
+}
+
+/// Position and length of the blank line ending the first SSE event in `buf`.
+fn event_end(buf: &[u8]) -> Option<(usize, usize)> {
+    let find = |sep: &[u8]| buf.windows(sep.len()).position(|w| w == sep).map(|i| (i, sep.len()));
+    match (find(b"\n\n"), find(b"\r\n\r\n")) {
+        (Some(a), Some(b)) => Some(if b.0 < a.0 { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The event name and data of one SSE block (`event:` / `data:` lines).
+fn parse_event(block: &str) -> Option<(String, String)> {
+    let mut event = None;
+    let mut data: Vec<&str> = Vec::new();
+    for line in block.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            event = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    Some((event?, data.join("\n")))
+}
+
+/// Partial update sent by a peer when only a session's status changed.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusUpdate {
+    id: String,
+    status: SessionStatus,
+    pane_id: Option<String>,
+    pane_verified: Option<bool>,
+    permission_message: Option<String>,
+    question_data: Option<serde_json::Value>,
+}
+
+fn apply_event(state: &AppState, index: usize, event: &str, data: &str) {
+    let changed = match event {
+        "sessions" | "sessionsUpdate" => {
+            let Ok(sessions) = serde_json::from_str::<Vec<Session>>(data) else {
+                return;
+            };
+            if event == "sessions" {
+                clear_peer(state, index);
+            }
+            for session in sessions {
+                upsert_session(state, index, session);
+            }
+            true
+        }
+        "sessionsRemoved" => {
+            let Ok(ids) = serde_json::from_str::<Vec<String>>(data) else {
+                return;
+            };
+            let before = state.peer_sessions.len();
+            for id in ids {
+                state.peer_sessions.remove_if(&id, |_, p| p.peer == index);
+            }
+            state.peer_sessions.len() != before
+        }
+        "statusUpdate" => {
+            let Ok(update) = serde_json::from_str::<StatusUpdate>(data) else {
+                return;
+            };
+            match state.peer_sessions.get_mut(&update.id) {
+                Some(mut entry) if entry.peer == index => {
+                    let session = &mut entry.session;
+                    session.status = update.status;
+                    session.pane_id = update.pane_id;
+                    session.pane_verified = update.pane_verified;
+                    session.permission_message = update.permission_message;
+                    session.question_data = update.question_data;
+                    entry.revision = now_ms();
+                    true
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+    if changed {
+        // Re-send the merged session list to SSE clients
+        let _ = state.history_tx.send(());
+    }
+}
+
+fn upsert_session(state: &AppState, index: usize, mut session: Session) {
+    // Sessions the peer itself federates are its peers' business
+    if session.host.is_some() {
+        return;
+    }
+    session.host = Some(state.peers[index].label.clone());
+    state.peer_sessions.insert(
+        session.id.clone(),
+        PeerSession {
+            peer: index,
+            session,
+            revision: now_ms(),
+        },
+    );
+}
+
+/// Local sessions plus every peer's, in session list order. Local sessions
+/// win if a peer reports the same id.
+pub fn merge_sessions(state: &AppState, mut sessions: Vec<Session>, tag: Option<&str>) -> Vec<Session> {
+    if state.peer_sessions.is_empty() {
+        return sessions;
+    }
+    let local: std::collections::HashSet<String> = sessions.iter().map(|s| s.id.clone()).collect();
+    sessions.extend(
+        state
+            .peer_sessions
+            .iter()
+            .filter(|p| !local.contains(p.key()))
+            .filter(|p| tag.is_none_or(|t| p.session.tags.iter().any(|tag| tag == t)))
+            .map(|p| p.session.clone()),
+    );
+    sort_sessions(&mut sessions);
+    sessions
+}
+
+/// When a peer session last changed, 0 for local sessions.
+pub fn revision(state: &AppState, session_id: &str) -> f64 {
+    state.peer_sessions.get(session_id).map(|p| p.revision).unwrap_or(0.0)
+}
+
+// --- Search ---
+
+/// Run a search on every peer at once. Unreachable peers are skipped.
+pub async fn search_peers(state: &AppState, query: &str) -> Vec<SearchResult> {
+    let client = reqwest::Client::new();
+    let mut searches = JoinSet::new();
+    for peer in state.peers.iter().cloned() {
+        let request = peer_request(&client, &peer, reqwest::Method::POST, "/api/search")
+            .timeout(PEER_SEARCH_TIMEOUT)
+            .json(&serde_json::json!({ "query": query }));
+        searches.spawn(async move {
+            #[derive(Deserialize)]
+            struct SearchResponse {
+                results: Vec<SearchResult>,
+            }
+            let response = request.send().await.map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("search returned {}", response.status()));
+            }
+            let body: SearchResponse = response.json().await.map_err(|e| e.to_string())?;
+            Ok::<_, String>((peer.label, body.results))
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = searches.join_next().await {
+        match joined {
+            Ok(Ok((label, peer_results))) => {
+                results.extend(peer_results.into_iter().map(|r| SearchResult {
+                    host: Some(label.clone()),
+                    ..r
+                }));
+            }
+            Ok(Err(e)) => eprintln!("[federation] search: {}", e),
+            Err(_) => {}
+        }
+    }
+    results
+}
+
+// --- Proxy ---
+
+/// The session id in `/api/conversation/:id/...` and `/api/sessions/:id/...`.
+fn session_id_of(path: &str) -> Option<&str> {
+    let rest = path
+        .strip_prefix("/api/conversation/")
+        .or_else(|| path.strip_prefix("/api/sessions/"))?;
+    let id = rest.split('/').next()?;
+    (!id.is_empty() && id != "stream").then_some(id)
+}
+
+/// Forward reads and actions on a peer's session to that peer.
+pub async fn proxy_peer_requests(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let owner = session_id_of(request.uri().path())
+        .filter(|id| !state.file_index.contains_key(*id))
+        .and_then(|id| state.peer_sessions.get(id).map(|p| p.peer));
+    let Some(index) = owner else {
+        return next.run(request).await;
+    };
+    match proxy(&state.peers[index], request).await {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("[federation] proxy to {}: {}", state.peers[index].label, e);
+            (StatusCode::BAD_GATEWAY, Json(serde_json::json!({ "error": e }))).into_response()
+        }
+    }
+}
+
+async fn proxy(peer: &Peer, request: Request) -> Result<Response, String> {
+    let method = reqwest::Method::from_bytes(request.method().as_str().as_bytes()).map_err(|e| e.to_string())?;
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+    let content_type = request.headers().get(header::CONTENT_TYPE).cloned();
+    let body = axum::body::to_bytes(request.into_body(), PROXY_BODY_LIMIT)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let client = reqwest::Client::new();
+    let mut upstream = peer_request(&client, peer, method, &path_and_query);
+    if let Some(content_type) = content_type {
+        upstream = upstream.header(header::CONTENT_TYPE, content_type.as_bytes());
+    }
+    let upstream = upstream.body(body).send().await.map_err(|e| e.to_string())?;
+
+    // Streamed through so SSE endpoints keep working
+    let mut response = Response::builder().status(upstream.status().as_u16());
+    for name in [header::CONTENT_TYPE, header::CONTENT_DISPOSITION, header::CACHE_CONTROL] {
+        if let Some(value) = upstream.headers().get(name.as_str()) {
+            response = response.header(name, value.as_bytes());
+        }
+    }
+    response
+        .body(Body::from_stream(upstream.bytes_stream()))
+        .map_err(|e| e.to_string())
+}
+
+// --- Token check ---
+
+/// With `--token`, API requests from other machines must carry it as a bearer
+/// token. Loopback clients (hooks, the local browser) are let through.
+pub async fn require_token(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(ref token) = state.api_token else {
+        return next.run(request).await;
+    };
+    let loopback = connect_info.is_some_and(|ConnectInfo(addr)| addr.ip().is_loopback());
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|t| t == token);
+    if loopback || authorized || !request.uri().path().starts_with("/api/") {
+        return next.run(request).await;
+    }
+    (StatusCode::UNAUTHORIZED, Json(serde_json::json!({ "error": "Missing or invalid token" }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_end_finds_the_first_blank_line() {
+        assert_eq!(event_end(b"event: a\ndata: 1\n\nevent: b"), Some((16, 2)));
+        assert_eq!(event_end(b"event: a\r\ndata: 1\r\n\r\n"), Some((17, 4)));
+        assert_eq!(event_end(b"event: a\ndata: 1\n"), None);
+    }
+
+    #[test]
+    fn multi_byte_characters_split_across_chunks_are_decoded() {
+        let event = "event: sessions\ndata: [\"caf\u{e9}\"]\n\n".as_bytes();
+        let split = event.iter().position(|&b| b == 0xc3).unwrap() + 1;
+        let mut buf: Vec<u8> = event[..split].to_vec();
+        assert_eq!(event_end(&buf), None);
+        buf.extend_from_slice(&event[split..]);
+        let (end, _) = event_end(&buf).unwrap();
+        let (name, data) = parse_event(&String::from_utf8_lossy(&buf[..end])).unwrap();
+        assert_eq!(name, "sessions");
+        assert_eq!(data, "[\"caf\u{e9}\"]");
+    }
+}
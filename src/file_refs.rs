@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use crate::models::{FileAccess, FileRef, FileSession};
+use crate::state::AppState;
+use crate::storage::get_sessions;
+
+/// Files referenced by one transcript, by absolute path.
+pub type FileRefs = BTreeMap<String, Vec<FileRef>>;
+
+/// Resolve `path` against `cwd` (and `~` against the home directory), then
+/// drop `.` / `..` components without touching the filesystem: the file may be
+/// gone, or on another machine.
+fn normalize_path(path: &str, cwd: Option<&str>) -> Option<String> {
+    let joined = match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()?.join(rest),
+        None if Path::new(path).is_absolute() => PathBuf::from(path),
+        None => Path::new(cwd?).join(path),
+    };
+    let mut normalized = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    Some(normalized.to_string_lossy().to_string())
+}
+
+fn has_extension(name: &str) -> bool {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => !stem.is_empty() && ext.len() <= 10 && ext.chars().any(|c| c.is_ascii_alphabetic()),
+        None => false,
+    }
+}
+
+/// Whether a shell word looks like a file path: absolute, explicitly relative
+/// or with a file extension, and no globs, variables or URLs. Bare relative
+/// paths need the extension so `s/a/b/g` and the like are left out.
+fn looks_like_path(word: &str) -> bool {
+    if word.is_empty()
+        || word.ends_with('/')
+        || word.contains("://")
+        || !word.chars().all(|c| c.is_alphanumeric() || "/._-+@~,".contains(c))
+    {
+        return false;
+    }
+    ["/", "~/", "./", "../"].iter().any(|prefix| word.starts_with(prefix))
+        || has_extension(word.rsplit('/').next().unwrap_or(word))
+}
+
+/// File paths mentioned in a shell command. A heuristic: commands can refer to
+/// files in ways no parser would follow.
+fn command_paths(command: &str) -> Vec<String> {
+    command
+        .split(|c: char| c.is_whitespace() || ";|&<>()".contains(c))
+        .map(|word| word.trim_matches(|c| c == '"' || c == '\''))
+        // `--output=path`
+        .map(|word| match word.split_once('=') {
+            Some((flag, value)) if flag.starts_with('-') => value,
+            _ => word,
+        })
+        .filter(|word| !word.starts_with('-') && looks_like_path(word))
+        .map(|word| word.to_string())
+        .collect()
+}
+
+/// Paths a tool call read, wrote or mentioned.
+fn tool_paths(tool: &str, input: &serde_json::Value) -> Vec<(String, FileAccess)> {
+    let str_field = |key: &str| input.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+    match tool {
+        "Read" => str_field("file_path").map(|p| (p, FileAccess::Read)).into_iter().collect(),
+        "Edit" | "MultiEdit" | "Write" => str_field("file_path").map(|p| (p, FileAccess::Write)).into_iter().collect(),
+        "NotebookEdit" => str_field("notebook_path").map(|p| (p, FileAccess::Write)).into_iter().collect(),
+        "Bash" => str_field("command")
+            .map(|c| command_paths(&c).into_iter().map(|p| (p, FileAccess::Bash)).collect())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Record the files referenced by the tool calls of one transcript line, and
+/// forget the calls whose tool_result is an error.
+pub fn record_line(refs: &mut Arc<FileRefs>, val: &serde_json::Value, cwd: Option<&str>) {
+    let Some(blocks) = val.pointer("/message/content").and_then(|c| c.as_array()) else {
+        return;
+    };
+    let str_field = |key: &str| val.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+    let block_field = |block: &serde_json::Value, key: &str| block.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+    let cwd = str_field("cwd").or_else(|| cwd.map(String::from));
+
+    match val.get("type").and_then(|t| t.as_str()) {
+        Some("assistant") => {
+            for block in blocks.iter().filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_use")) {
+                let (Some(tool), Some(input)) = (block_field(block, "name"), block.get("input")) else {
+                    continue;
+                };
+                for (path, access) in tool_paths(&tool, input) {
+                    let Some(path) = normalize_path(&path, cwd.as_deref()) else { continue };
+                    Arc::make_mut(refs).entry(path).or_default().push(FileRef {
+                        tool: tool.clone(),
+                        access,
+                        tool_use_id: block_field(block, "id"),
+                        uuid: str_field("uuid"),
+                        timestamp: str_field("timestamp"),
+                        agent_id: None,
+                    });
+                }
+            }
+        }
+        Some("user") => {
+            for block in blocks {
+                let failed = block.get("type").and_then(|t| t.as_str()) == Some("tool_result")
+                    && block.get("is_error").and_then(|e| e.as_bool()) == Some(true);
+                let Some(id) = block_field(block, "tool_use_id").filter(|_| failed) else {
+                    continue;
+                };
+                let called = |r: &FileRef| r.tool_use_id.as_deref() == Some(id.as_str());
+                if !refs.values().flatten().any(called) {
+                    continue;
+                }
+                Arc::make_mut(refs).retain(|_, file_refs| {
+                    file_refs.retain(|r| !called(r));
+                    !file_refs.is_empty()
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether an indexed path is the one asked for. Relative queries match any
+/// path ending with them.
+fn path_matches(indexed: &str, query: &str) -> bool {
+    if Path::new(query).is_absolute() {
+        indexed == query
+    } else {
+        indexed.strip_suffix(query).is_some_and(|prefix| prefix.ends_with('/'))
+    }
+}
+
+/// Move `key` in the path → sessions index from the paths of `old` to those of `new`.
+pub fn update_index(state: &AppState, key: &str, old: &FileRefs, new: &FileRefs) {
+    for path in old.keys().filter(|p| !new.contains_key(*p)) {
+        state.file_ref_index.remove_if_mut(path, |_, keys| {
+            keys.remove(key);
+            keys.is_empty()
+        });
+    }
+    for path in new.keys().filter(|p| !old.contains_key(*p)) {
+        state.file_ref_index.entry(path.clone()).or_default().insert(key.to_string());
+    }
+}
+
+/// Sessions (subagents included) whose tool calls read, wrote or mentioned a
+/// file, in session list order.
+pub async fn get_file_sessions(state: &AppState, path: &str) -> Vec<FileSession> {
+    get_paths_sessions(state, &[path]).await
+}
+
+/// Like `get_file_sessions`, for any of several paths (e.g. a file and its
+/// aliases). Looked up in the path index, which the session list refresh and
+/// the subagent watcher keep current.
+pub async fn get_paths_sessions(state: &AppState, paths: &[&str]) -> Vec<FileSession> {
+    let queries: Vec<String> = paths
+        .iter()
+        .map(|path| match Path::new(path).is_absolute() {
+            true => normalize_path(path, None).unwrap_or_default(),
+            false => path.trim_start_matches("./").trim_end_matches('/').to_string(),
+        })
+        .filter(|q| !q.is_empty())
+        .collect();
+    if queries.is_empty() {
+        return Vec::new();
+    }
+    let sessions = get_sessions(state, None).await;
+
+    // sessionId → (session_meta key, matching paths)
+    let mut matched: HashMap<String, Vec<(String, Vec<String>)>> = HashMap::new();
+    let mut add = |path: &str, keys: &HashSet<String>| {
+        for key in keys {
+            let session_id = key.split_once('/').map_or(key.as_str(), |(id, _)| id);
+            let entries = matched.entry(session_id.to_string()).or_default();
+            match entries.iter_mut().find(|(k, _)| k == key) {
+                Some((_, paths)) if !paths.iter().any(|p| p == path) => paths.push(path.to_string()),
+                Some(_) => {}
+                None => entries.push((key.clone(), vec![path.to_string()])),
+            }
+        }
+    };
+    for query in &queries {
+        if Path::new(query).is_absolute() {
+            if let Some(keys) = state.file_ref_index.get(query) {
+                add(query, keys.value());
+            }
+        } else {
+            for entry in state.file_ref_index.iter().filter(|e| path_matches(e.key(), query)) {
+                add(entry.key(), entry.value());
+            }
+        }
+    }
+
+    let mut results = Vec::new();
+    for session in sessions {
+        let Some(keys) = matched.remove(&session.id) else {
+            continue;
+        };
+        let mut paths = Vec::new();
+        let mut refs = Vec::new();
+        for (key, key_paths) in keys {
+            // Subagent entries are `<sessionId>/agent-<id>`
+            let agent_id = key.split_once('/').map(|(_, stem)| stem.trim_start_matches("agent-").to_string());
+            let Some(meta) = state.session_meta.get(&key) else {
+                continue;
+            };
+            for path in key_paths {
+                let Some(file_refs) = meta.file_refs.get(&path) else {
+                    continue;
+                };
+                refs.extend(file_refs.iter().cloned().map(|r| FileRef {
+                    agent_id: agent_id.clone(),
+                    ..r
+                }));
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        if refs.is_empty() {
+            continue;
+        }
+        refs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+
+        results.push(FileSession {
+            modified: refs.iter().any(|r| r.access == FileAccess::Write),
+            source: state.source_label(&session.id),
+            session_id: session.id,
+            display: session.display,
+            project_name: session.project_name,
+            timestamp: session.timestamp,
+            paths,
+            refs,
+        });
+    }
+    results
+}
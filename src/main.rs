@@ -1,15 +1,33 @@
+mod agent_blame;
+mod analytics;
+mod bundle;
+mod changes;
+mod compression;
+mod conversation_tree;
 mod embedded;
+mod export;
+mod federation;
+mod file_refs;
 mod models;
+mod plans;
 mod push;
+mod retention;
+mod search_index;
+mod search_query;
 mod server;
+mod session_meta;
 mod state;
 mod storage;
+mod subagent_tree;
 mod summarizer;
 mod tls;
+mod tool_calls;
+mod trash;
 mod tts;
+mod user_meta;
 mod watcher;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(name = "claude-run")]
@@ -20,9 +38,10 @@ struct Cli {
     #[arg(short, long, default_value = "12001")]
     port: u16,
 
-    /// Claude directory path
-    #[arg(short, long, default_value_t = default_claude_dir())]
-    dir: String,
+    /// Claude directory path, repeatable to show several. Optionally labelled
+    /// (`work=~/.claude-work`); the first one also holds claude-run's own data
+    #[arg(short, long, global = true, value_name = "[LABEL=]DIR", value_parser = parse_source)]
+    dir: Vec<state::Source>,
 
     /// Enable CORS for development
     #[arg(long)]
@@ -39,6 +58,65 @@ struct Cli {
     /// Tailscale hostname (skips `tailscale status` call)
     #[arg(long)]
     hostname: Option<String>,
+
+    /// Permanently delete trashed sessions after this many days
+    #[arg(long)]
+    trash_retention_days: Option<u64>,
+
+    /// Compress transcripts untouched for this many days
+    #[arg(long)]
+    compress_after_days: Option<u64>,
+
+    /// Compression used for old transcripts (zstd or gzip)
+    #[arg(long, default_value = "zstd", value_parser = parse_codec)]
+    compression: compression::Codec,
+
+    /// Delete transcripts untouched for this many days
+    #[arg(long)]
+    delete_after_days: Option<u64>,
+
+    /// JSON list of peer claude-run instances to merge sessions from
+    /// [default: <dir>/peers.json]
+    #[arg(long, value_name = "FILE")]
+    peers: Option<String>,
+
+    /// Require this bearer token from API clients other than localhost
+    #[arg(long)]
+    token: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render all sessions into a static, read-only HTML site
+    ExportSite {
+        /// Output directory
+        outdir: String,
+
+        /// Only export sessions of this project (path or name)
+        #[arg(long)]
+        project: Option<String>,
+
+        /// Include thinking blocks
+        #[arg(long)]
+        thinking: bool,
+
+        /// Inline subagent transcripts
+        #[arg(long)]
+        subagents: bool,
+    },
+
+    /// Import a session bundle (.tar.gz) exported from another machine
+    Import {
+        /// Bundle file
+        bundle: String,
+
+        /// Project path to import into (defaults to the one recorded in the bundle)
+        #[arg(long)]
+        project: Option<String>,
+    },
 }
 
 fn default_claude_dir() -> String {
@@ -47,26 +125,100 @@ fn default_claude_dir() -> String {
         .unwrap_or_else(|| "~/.claude".to_string())
 }
 
+/// `[LABEL=]DIR`. Without a label, the directory name is used (`.claude-work` → `claude-work`).
+fn parse_source(s: &str) -> Result<state::Source, String> {
+    let (label, dir) = match s.split_once('=') {
+        Some((label, dir)) if !label.is_empty() && !label.contains('/') => (Some(label), dir),
+        _ => (None, s),
+    };
+    let dir = dir.trim_end_matches('/');
+    if dir.is_empty() {
+        return Err("empty directory".to_string());
+    }
+    let label = label.map(str::to_string).unwrap_or_else(|| {
+        let name = std::path::Path::new(dir)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| dir.to_string());
+        name.trim_start_matches('.').to_string()
+    });
+    Ok(state::Source::new(label, dir.to_string()))
+}
+
+/// The `--dir` sources, `~/.claude` if none was given. Labels must be unique.
+fn sources(dirs: &[state::Source]) -> anyhow::Result<Vec<state::Source>> {
+    if dirs.is_empty() {
+        return Ok(vec![state::Source::new("claude".to_string(), default_claude_dir())]);
+    }
+    for (i, source) in dirs.iter().enumerate() {
+        if dirs[..i].iter().any(|s| s.label == source.label) {
+            anyhow::bail!("duplicate --dir label '{}', name them with LABEL=DIR", source.label);
+        }
+    }
+    Ok(dirs.to_vec())
+}
+
+fn parse_codec(s: &str) -> Result<compression::Codec, String> {
+    compression::Codec::parse(s).ok_or_else(|| format!("unknown compression '{}', expected zstd or gzip", s))
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    let sources = sources(&cli.dir)?;
+    let claude_dir = sources[0].claude_dir.clone();
+
+    if let Some(Command::ExportSite { ref outdir, ref project, thinking, subagents }) = cli.command {
+        let options = export::ExportOptions {
+            thinking,
+            tool_results: true,
+            subagents,
+        };
+        return export_site(sources, outdir, project.as_deref(), options).await;
+    }
+    if let Some(Command::Import { ref bundle, ref project }) = cli.command {
+        return import_bundle(sources, bundle, project.as_deref()).await;
+    }
 
     // Load or generate VAPID keys for push notifications
-    let (vapid_pem, vapid_pub) = push::load_or_generate_vapid(&cli.dir)
+    let (vapid_pem, vapid_pub) = push::load_or_generate_vapid(&claude_dir)
         .unwrap_or_else(|e| {
             eprintln!("[push] Failed to load/generate VAPID keys: {}", e);
             (Vec::new(), String::new())
         });
 
-    let state = state::AppState::new(cli.dir.clone(), cli.dev, vapid_pem, vapid_pub);
+    let peers_path = cli.peers.clone().unwrap_or_else(|| format!("{}/peers.json", claude_dir));
+    let peers = federation::load_peers(&peers_path).await;
+    let state = state::AppState::new(sources, peers, cli.token.clone(), cli.dev, vapid_pem, vapid_pub);
 
     // Load push subscriptions
-    for sub in push::load_subscriptions(&cli.dir) {
+    for sub in push::load_subscriptions(&claude_dir) {
         state.push_subscriptions.insert(sub.endpoint.clone(), sub);
     }
 
-    // Load storage (file index + history cache)
+    // Load the session catalog from the last run, then storage (file index + history cache)
+    session_meta::load_catalog(&state).await;
     storage::load_storage(&state).await;
+    session_meta::spawn_catalog_sync(state.clone());
+    user_meta::load_user_meta(&state).await;
+    if let Some(days) = cli.trash_retention_days {
+        trash::spawn_trash_purger(state.clone(), std::time::Duration::from_secs(days * 86400));
+    }
+    if cli.compress_after_days.is_some() || cli.delete_after_days.is_some() {
+        let days = |d: u64| std::time::Duration::from_secs(d * 86400);
+        retention::spawn_retention(
+            state.clone(),
+            retention::RetentionPolicy {
+                compress_after: cli.compress_after_days.map(days),
+                codec: cli.compression,
+                delete_after: cli.delete_after_days.map(days),
+            },
+        );
+    }
+
+    // Load the search index and keep it up to date in the background
+    search_index::load_index(&state).await;
+    search_index::spawn_indexer(state.clone());
 
     // Start file watcher
     watcher::start_watcher(state.clone()).await?;
@@ -76,6 +228,9 @@ async fn main() -> anyhow::Result<()> {
     summarizer::spawn_summarizer(state.clone());
     summarizer::spawn_initial_summary_scan(state.clone());
 
+    // Follow peer instances
+    federation::spawn_peer_sync(state.clone());
+
     // Build router
     let app = server::create_router(state.clone());
 
@@ -107,7 +262,9 @@ async fn main() -> anyhow::Result<()> {
         let http_listener =
             tokio::net::TcpListener::bind(format!("127.0.0.1:{}", cli.port)).await?;
         tokio::spawn(async move {
-            axum::serve(http_listener, http_app).await.ok();
+            axum::serve(http_listener, http_app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                .await
+                .ok();
         });
 
         // HTTPS on all interfaces
@@ -122,8 +279,10 @@ async fn main() -> anyhow::Result<()> {
 
         axum_server::bind_rustls(tls_addr, tls_config)
             .handle(handle)
-            .serve(app.into_make_service())
+            .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
             .await?;
+        search_index::save_index(&state).await;
+        session_meta::save_catalog(&state).await;
     } else {
         // HTTP mode (default)
         let url = if cli.dev {
@@ -140,14 +299,52 @@ async fn main() -> anyhow::Result<()> {
 
         let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", cli.port)).await?;
 
-        axum::serve(listener, app)
+        axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
             .with_graceful_shutdown(shutdown_signal())
             .await?;
+        search_index::save_index(&state).await;
+        session_meta::save_catalog(&state).await;
     }
 
     Ok(())
 }
 
+async fn export_site(
+    sources: Vec<state::Source>,
+    outdir: &str,
+    project: Option<&str>,
+    options: export::ExportOptions,
+) -> anyhow::Result<()> {
+    let state = state::AppState::new(sources, Vec::new(), None, false, Vec::new(), String::new());
+    session_meta::load_catalog(&state).await;
+    storage::load_storage(&state).await;
+    summarizer::load_summaries(&state).await;
+    user_meta::load_user_meta(&state).await;
+
+    let count = export::export_site(&state, outdir, project, options)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    session_meta::save_catalog(&state).await;
+    println!("Exported {} sessions to {}/index.html", count, outdir);
+    Ok(())
+}
+
+async fn import_bundle(sources: Vec<state::Source>, bundle_path: &str, project: Option<&str>) -> anyhow::Result<()> {
+    let data = tokio::fs::read(bundle_path).await?;
+    let state = state::AppState::new(sources, Vec::new(), None, false, Vec::new(), String::new());
+    storage::load_storage(&state).await;
+
+    let result = bundle::import_bundle(&state, &data, project)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    println!(
+        "Imported session {} into {} ({} subagents, {} plans)",
+        result.session_id, result.project, result.subagents, result.plans
+    );
+    println!("Resume it with: cd {} && claude --resume {}", result.project, result.session_id);
+    Ok(())
+}
+
 async fn shutdown_signal() {
     tokio::signal::ctrl_c()
         .await
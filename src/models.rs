@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -7,6 +9,9 @@ pub struct HistoryEntry {
     pub project: String,
     #[serde(rename = "sessionId", skip_serializing_if = "Option::is_none")]
     pub session_id: Option<String>,
+    /// Index of the source whose history.jsonl this entry came from
+    #[serde(skip)]
+    pub source: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -50,6 +55,45 @@ pub struct Session {
     pub summary: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file_size: Option<u64>,
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// User-set title, shown instead of the summary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Label of the Claude home directory, when more than one is shown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Label of the peer claude-run instance the session lives on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+/// Organization added by the user: stars, pins, tags, a custom title and notes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionUserMeta {
+    pub starred: bool,
+    pub pinned: bool,
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Markdown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// ms since the epoch
+    pub updated_at: f64,
+}
+
+impl SessionUserMeta {
+    pub fn is_empty(&self) -> bool {
+        !self.starred && !self.pinned && self.tags.is_empty() && self.title.is_none() && self.notes.is_none()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,6 +106,46 @@ pub struct TokenUsage {
     pub cache_read_input_tokens: Option<u64>,
 }
 
+/// Summed token usage over a number of API responses.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageTotals {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    /// Number of API responses
+    pub requests: u64,
+}
+
+impl UsageTotals {
+    pub fn from_usage(usage: &TokenUsage) -> Self {
+        Self {
+            input_tokens: usage.input_tokens.unwrap_or(0),
+            output_tokens: usage.output_tokens.unwrap_or(0),
+            cache_creation_input_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
+            cache_read_input_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+            requests: 1,
+        }
+    }
+
+    pub fn add(&mut self, other: &UsageTotals) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        self.requests += other.requests;
+    }
+
+    pub fn sub(&mut self, other: &UsageTotals) {
+        self.input_tokens = self.input_tokens.saturating_sub(other.input_tokens);
+        self.output_tokens = self.output_tokens.saturating_sub(other.output_tokens);
+        self.cache_creation_input_tokens = self.cache_creation_input_tokens.saturating_sub(other.cache_creation_input_tokens);
+        self.cache_read_input_tokens = self.cache_read_input_tokens.saturating_sub(other.cache_read_input_tokens);
+        self.requests = self.requests.saturating_sub(other.requests);
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContentBlock {
     #[serde(rename = "type")]
@@ -136,6 +220,102 @@ pub struct SubagentInfo {
     pub tool_use_id: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanStatus {
+    /// No answer yet
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// An ExitPlanMode call recorded from a transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanRecord {
+    pub tool_use_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    pub plan: String,
+    pub status: PlanStatus,
+}
+
+/// Every version of one plan: the plans proposed by the sessions sharing a
+/// slug, and its plan files.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanHistory {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub versions: Vec<PlanVersion>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanVersion {
+    /// 1-based, oldest first
+    pub version: usize,
+    /// Session that proposed it, None for a plan file no session proposed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_use_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_uuid: Option<String>,
+    /// Plan file holding this text, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// None for plan files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<PlanStatus>,
+    pub plan: String,
+    /// Unified diff from the previous version, empty for the first
+    pub diff: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubagentStatus {
+    Running,
+    Finished,
+    Errored,
+}
+
+/// A Task call and the agent it ran, with the agents that one spawned.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubagentNode {
+    /// None until the agent has written its transcript
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// None for transcripts no Task call could be found for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_use_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subagent_type: Option<String>,
+    pub status: SubagentStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<String>,
+    /// Until the result, or the agent's latest message while running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    /// The agent's own usage, children excluded
+    pub usage: UsageTotals,
+    /// Tool calls by tool name, children excluded
+    pub tool_calls: BTreeMap<String, usize>,
+    pub children: Vec<SubagentNode>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PlanSessionInfo {
@@ -150,8 +330,69 @@ pub struct StreamResult {
     pub next_offset: u64,
 }
 
+/// Context size after one API response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextPoint {
+    pub turn: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// input + cache creation + cache read tokens
+    pub context_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    /// Share of the context served from cache (0..1)
+    pub cache_read_ratio: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactionMarker {
+    /// Turn of the first point after the compaction
+    pub turn: usize,
+    /// Byte offset of the compaction record, unique per marker
+    pub offset: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextTimeline {
+    pub points: Vec<ContextPoint>,
+    pub compactions: Vec<CompactionMarker>,
+    pub peak_context_tokens: u64,
+    /// Byte offset after the last parsed line (resume point for the stream)
+    pub end_offset: u64,
+}
+
+/// A deleted session that can still be restored.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct TrashedSession {
+    pub id: String,
+    pub display: String,
+    pub project: String,
+    pub project_name: String,
+    /// ms since the epoch; unknown for sessions deleted before the trash existed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<f64>,
+    pub message_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PaginatedResult {
     pub messages: Vec<ConversationMessage>,
     /// Byte offset where these messages start (use to load older)
@@ -160,12 +401,224 @@ pub struct PaginatedResult {
     pub end_offset: u64,
     /// True if there are older messages before start_offset
     pub has_more: bool,
+    /// True if newer messages after end_offset were left out (windows around a message)
+    #[serde(default)]
+    pub has_newer: bool,
+}
+
+/// A `tool_use` block paired with its `tool_result`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCall {
+    pub tool_use_id: String,
+    /// None when only the result has been seen (e.g. an SSE stream resumed past the call)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ContentBlockContent>,
+    pub is_error: bool,
+    /// True once the tool_result has arrived
+    pub completed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    /// Assistant message carrying the tool_use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_uuid: Option<String>,
+    /// User message carrying the tool_result
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_uuid: Option<String>,
+}
+
+/// Every file change of a session (and its subagents), grouped by file.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionChanges {
+    pub session_id: String,
+    pub files: Vec<FileChanges>,
+    /// Unified diff of every file
+    pub diff: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChanges {
+    pub path: String,
+    pub changes: Vec<FileChange>,
+    /// Unified diff from before the first change to after the last one. When
+    /// not `complete`, the per-change diffs joined instead
+    pub diff: String,
+    /// False when the content before the first change is unknown or a change
+    /// could not be replayed
+    pub complete: bool,
+    /// The session created the file
+    pub created: bool,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// One successful Edit, MultiEdit, Write or NotebookEdit call.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChange {
+    pub tool_use_id: String,
+    pub tool: String,
+    /// Subagent that made the change, None for the session itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// Diff of this change alone. Hunk line numbers are relative to the edited
+    /// snippet when the file content at that point is unknown
+    pub diff: String,
+}
+
+/// How a tool call referenced a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileAccess {
+    Read,
+    Write,
+    /// Mentioned in a Bash command
+    Bash,
+}
+
+/// A tool call in a transcript that referenced a file path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileRef {
+    pub tool: String,
+    pub access: FileAccess,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_use_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// Subagent that made the call, None for the session itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+}
+
+/// A session whose tool calls referenced a file.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSession {
+    pub session_id: String,
+    pub display: String,
+    pub project_name: String,
+    pub timestamp: f64,
+    /// Files matching the query, for a relative query path
+    pub paths: Vec<String>,
+    pub refs: Vec<FileRef>,
+    pub modified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// `git blame` of a working-tree file, with the lines a session wrote.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentBlame {
+    pub path: String,
+    pub lines: Vec<BlameLine>,
+    /// Sessions with attributed lines, most lines first
+    pub sessions: Vec<BlameSession>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameLine {
+    /// 1-based
+    pub line: usize,
+    pub content: String,
+    /// None for uncommitted lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<BlameCommit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<LineAuthor>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameCommit {
+    pub sha: String,
+    pub author: String,
+    /// Author time, seconds since the epoch
+    pub time: i64,
+    pub summary: String,
+}
+
+/// The tool call whose written text contains a line.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineAuthor {
+    pub session_id: String,
+    pub tool: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameSession {
+    pub session_id: String,
+    pub display: String,
+    pub lines: usize,
+}
+
+/// A displayed message in the conversation DAG built from `parentUuid`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeNode {
+    pub uuid: String,
+    /// Nearest displayed ancestor (progress/system lines are skipped)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_uuid: Option<String>,
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    pub is_sidechain: bool,
+    pub on_active_branch: bool,
+    pub children: Vec<String>,
+    pub preview: String,
+    /// Byte offset of the message line (usable with /around and /older)
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationTree {
+    /// Nodes in file order
+    pub nodes: Vec<TreeNode>,
+    pub roots: Vec<String>,
+    /// Nodes with more than one child (rewinds, edited prompts, sidechains)
+    pub branch_points: Vec<String>,
+    /// Last main-chain message, the one a resume continues from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_leaf: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchMatch {
     pub message_index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    /// Byte offset of the message line, for `/api/conversation/:id/around`
+    pub offset: u64,
     pub text: String,
     pub snippet: String,
 }
@@ -178,6 +631,19 @@ pub struct SearchResult {
     pub project_name: String,
     pub timestamp: f64,
     pub matches: Vec<SearchMatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+/// Totals sent with the final `done` event of a streamed search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchTotals {
+    pub sessions_searched: usize,
+    pub sessions_matched: usize,
+    pub matches: usize,
 }
 
 // Raw JSON line struct for subagent detection
@@ -240,6 +706,14 @@ pub struct UsageResponse {
     pub extra_usage_cents: Option<f64>,
 }
 
+/// A Claude home directory shown in the dashboard.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceInfo {
+    pub label: String,
+    pub claude_dir: String,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LaunchRequest {
@@ -247,6 +721,8 @@ pub struct LaunchRequest {
     pub prompt: Option<String>,
     pub dangerously_skip_permissions: Option<bool>,
     pub zellij_session: Option<String>,
+    /// Label of the Claude home directory to start the session in
+    pub source: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,260 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use tokio::fs;
+
+use crate::changes::unified_diff;
+use crate::models::{PlanHistory, PlanRecord, PlanStatus, PlanVersion};
+use crate::state::AppState;
+use crate::storage::get_sessions;
+
+const EXIT_PLAN_TOOL: &str = "ExitPlanMode";
+
+/// Record the plans proposed in one transcript line, and whether the user
+/// approved them: a rejected ExitPlanMode comes back as an error result.
+pub fn record_line(plans: &mut Arc<Vec<PlanRecord>>, val: &serde_json::Value) {
+    let Some(blocks) = val.pointer("/message/content").and_then(|c| c.as_array()) else {
+        return;
+    };
+    let str_field = |key: &str| val.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+    let block_field = |block: &serde_json::Value, key: &str| block.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+
+    match val.get("type").and_then(|t| t.as_str()) {
+        Some("assistant") => {
+            for block in blocks.iter().filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_use")) {
+                if block.get("name").and_then(|n| n.as_str()) != Some(EXIT_PLAN_TOOL) {
+                    continue;
+                }
+                let (Some(id), Some(plan)) = (block_field(block, "id"), block.pointer("/input/plan").and_then(|p| p.as_str())) else {
+                    continue;
+                };
+                // Claude Code writes one line per content block and may repeat them
+                if plans.iter().any(|p| p.tool_use_id == id) {
+                    continue;
+                }
+                Arc::make_mut(plans).push(PlanRecord {
+                    tool_use_id: id,
+                    uuid: str_field("uuid"),
+                    timestamp: str_field("timestamp"),
+                    plan: plan.to_string(),
+                    status: PlanStatus::Pending,
+                });
+            }
+        }
+        Some("user") => {
+            for block in blocks.iter().filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result")) {
+                let Some(id) = block.get("tool_use_id").and_then(|v| v.as_str()) else { continue };
+                if let Some(i) = plans.iter().position(|p| p.tool_use_id == id) {
+                    Arc::make_mut(plans)[i].status = match block.get("is_error").and_then(|e| e.as_bool()) {
+                        Some(true) => PlanStatus::Rejected,
+                        _ => PlanStatus::Approved,
+                    };
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+pub struct PlanFilter {
+    /// Only the plan this session worked on, across every session sharing its slug
+    pub session: Option<String>,
+    /// Only plans of sessions in this project (exact path)
+    pub project: Option<String>,
+    /// Case-insensitive text every returned version contains
+    pub q: Option<String>,
+}
+
+/// A plan file under `<claude dir>/plans/`.
+struct PlanFile {
+    /// File stem: the session slug, with `-agent-<id>` for a subagent's plan
+    key: String,
+    path: String,
+    content: String,
+    timestamp: Option<String>,
+}
+
+async fn read_plan_files(state: &AppState) -> Vec<PlanFile> {
+    let mut files = Vec::new();
+    let mut seen_dirs = HashSet::new();
+    for source in &state.sources {
+        let dir = format!("{}/plans", source.claude_dir);
+        if !seen_dirs.insert(dir.clone()) {
+            continue;
+        }
+        let Ok(mut entries) = fs::read_dir(&dir).await else { continue };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("md") {
+                continue;
+            }
+            let Some(key) = path.file_stem().and_then(|s| s.to_str()).map(String::from) else {
+                continue;
+            };
+            let Ok(content) = fs::read_to_string(&path).await else { continue };
+            let timestamp = entry
+                .metadata()
+                .await
+                .and_then(|m| m.modified())
+                .ok()
+                .map(|t| DateTime::<Utc>::from(t).to_rfc3339_opts(SecondsFormat::Millis, true));
+            files.push(PlanFile {
+                key,
+                path: path.to_string_lossy().to_string(),
+                content,
+                timestamp,
+            });
+        }
+    }
+    files
+}
+
+/// Versions of one plan, before numbering.
+struct Group {
+    key: String,
+    slug: Option<String>,
+    project: Option<String>,
+    versions: Vec<PlanVersion>,
+}
+
+fn group_index(groups: &[Group], key: &str) -> Option<usize> {
+    groups.iter().position(|g| g.key == key)
+}
+
+/// Every plan, as successive versions each diffed against the previous one:
+/// the ExitPlanMode calls of sessions sharing a slug (or of a session without
+/// one), and the plan files under each Claude home directory. A plan file
+/// whose text a session proposed is folded into that version. Newest first.
+pub async fn get_plans(state: &AppState, filter: &PlanFilter) -> Vec<PlanHistory> {
+    let key_of = |session_id: &str, slug: Option<&String>| slug.cloned().unwrap_or_else(|| session_id.to_string());
+    let wanted = match &filter.session {
+        Some(id) => match state.session_meta.get(id) {
+            Some(meta) => Some(key_of(id, meta.slug.as_ref())),
+            None => return Vec::new(),
+        },
+        None => None,
+    };
+
+    // Listing sessions brings their metadata up to date; plans come from it
+    // (and from the details files saved with the catalog), not the transcripts
+    let mut groups: Vec<Group> = Vec::new();
+    for session in get_sessions(state, None).await {
+        if filter.project.as_ref().is_some_and(|p| *p != session.project) {
+            continue;
+        }
+        let Some((slug, plans)) = state
+            .session_meta
+            .get(&session.id)
+            .map(|m| (m.slug.clone(), m.plans.clone()))
+        else {
+            continue;
+        };
+        let key = key_of(&session.id, slug.as_ref());
+        if wanted.as_ref().is_some_and(|w| *w != key) {
+            continue;
+        }
+        // Kept even without ExitPlanMode calls, for the session's plan files
+        let i = match group_index(&groups, &key) {
+            Some(i) => i,
+            None => {
+                groups.push(Group {
+                    key: key.clone(),
+                    slug: slug.clone(),
+                    project: Some(session.project.clone()),
+                    versions: Vec::new(),
+                });
+                groups.len() - 1
+            }
+        };
+        groups[i].versions.extend(plans.iter().map(|p| PlanVersion {
+            version: 0,
+            session_id: Some(session.id.clone()),
+            tool_use_id: Some(p.tool_use_id.clone()),
+            message_uuid: p.uuid.clone(),
+            path: None,
+            timestamp: p.timestamp.clone(),
+            status: Some(p.status),
+            plan: p.plan.clone(),
+            diff: String::new(),
+        }));
+    }
+
+    let filtered = filter.session.is_some() || filter.project.is_some();
+    for file in read_plan_files(state).await {
+        // A subagent's plan file is its own plan, shown with its session's
+        let base = file.key.split_once("-agent-").map_or(file.key.as_str(), |(base, _)| base);
+        let known = groups.iter().any(|g| g.key == file.key || g.slug.as_deref() == Some(base));
+        if filtered && !known && wanted.as_deref() != Some(base) {
+            continue;
+        }
+        let found = group_index(&groups, &file.key);
+        if let Some(version) = found
+            .and_then(|i| groups[i].versions.iter_mut().rev().find(|v| v.path.is_none() && v.plan.trim() == file.content.trim()))
+        {
+            version.path = Some(file.path);
+            continue;
+        }
+        let i = match found {
+            Some(i) => i,
+            None => {
+                let project = groups.iter().find(|g| g.slug.as_deref() == Some(base)).and_then(|g| g.project.clone());
+                groups.push(Group {
+                    key: file.key.clone(),
+                    slug: Some(file.key.clone()),
+                    project,
+                    versions: Vec::new(),
+                });
+                groups.len() - 1
+            }
+        };
+        groups[i].versions.push(PlanVersion {
+            version: 0,
+            session_id: None,
+            tool_use_id: None,
+            message_uuid: None,
+            path: Some(file.path),
+            timestamp: file.timestamp,
+            status: None,
+            plan: file.content,
+            diff: String::new(),
+        });
+    }
+
+    let query = filter.q.as_ref().map(|q| q.to_lowercase()).filter(|q| !q.is_empty());
+    let mut plans: Vec<PlanHistory> = groups
+        .into_iter()
+        .filter_map(|mut group| {
+            group.versions.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+            let mut previous: Option<&str> = None;
+            let mut diffs = Vec::with_capacity(group.versions.len());
+            for (i, version) in group.versions.iter().enumerate() {
+                let name = |n: usize| format!("{}/v{}", group.key, n);
+                // ExitPlanMode plans lack the final newline plan files have
+                let with_newline = |text: &str| format!("{}\n", text.trim_end_matches('\n'));
+                diffs.push(match previous {
+                    Some(previous) => unified_diff(&with_newline(previous), &with_newline(&version.plan), &name(i), &name(i + 1)),
+                    None => String::new(),
+                });
+                previous = Some(&version.plan);
+            }
+            for (i, (version, diff)) in group.versions.iter_mut().zip(diffs).enumerate() {
+                version.version = i + 1;
+                version.diff = diff;
+            }
+            if let Some(ref query) = query {
+                group.versions.retain(|v| v.plan.to_lowercase().contains(query));
+            }
+            (!group.versions.is_empty()).then_some(PlanHistory {
+                slug: group.slug,
+                project: group.project,
+                versions: group.versions,
+            })
+        })
+        .collect();
+    plans.sort_by(|a, b| {
+        let latest = |p: &PlanHistory| p.versions.iter().filter_map(|v| v.timestamp.clone()).max();
+        latest(b).cmp(&latest(a))
+    });
+    plans
+}
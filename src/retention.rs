@@ -0,0 +1,115 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::fs;
+
+use crate::compression::{compress_file, decompress_file, is_compressed, Codec};
+use crate::state::AppState;
+use crate::storage::{self, find_session_file};
+use crate::trash;
+
+const RETENTION_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    /// Compress transcripts untouched for this long
+    pub compress_after: Option<Duration>,
+    pub codec: Codec,
+    /// Delete transcripts untouched for this long
+    pub delete_after: Option<Duration>,
+}
+
+/// Point the file index and cached metadata at a transcript's new path.
+fn move_transcript(state: &AppState, session_id: &str, old_path: &str, new_path: &str, file_size: u64) {
+    state.file_index.insert(session_id.to_string(), new_path.to_string());
+    if let Some(mut meta) = state.session_meta.get_mut(session_id) {
+        if meta.path == old_path {
+            meta.path = new_path.to_string();
+            meta.file_size = file_size;
+            state.catalog_dirty.store(true, Ordering::Release);
+        }
+    }
+    state.search_index.schedule(session_id, new_path);
+}
+
+async fn compress_session(state: &AppState, session_id: &str, file_path: &str, codec: Codec) -> Result<(), String> {
+    let new_path = compress_file(file_path, codec)
+        .await
+        .map_err(|e| format!("Failed to compress {}: {}", file_path, e))?;
+    let file_size = fs::metadata(&new_path).await.map(|m| m.len()).unwrap_or(0);
+    move_transcript(state, session_id, file_path, &new_path, file_size);
+    Ok(())
+}
+
+/// Make sure a session's transcript is a plain `.jsonl` again, so `claude
+/// --resume` can append to it.
+pub async fn ensure_uncompressed(state: &AppState, session_id: &str) -> Result<(), String> {
+    let Some(file_path) = find_session_file(state, session_id).await else {
+        return Ok(());
+    };
+    if !is_compressed(&file_path) {
+        return Ok(());
+    }
+    let new_path = decompress_file(&file_path)
+        .await
+        .map_err(|e| format!("Failed to decompress {}: {}", file_path, e))?;
+    let file_size = fs::metadata(&new_path).await.map(|m| m.len()).unwrap_or(0);
+    move_transcript(state, session_id, &file_path, &new_path, file_size);
+    eprintln!("[retention] decompressed {}", session_id);
+    Ok(())
+}
+
+/// One pass over every known transcript. Returns (compressed, deleted).
+async fn apply_policy(state: &AppState, policy: &RetentionPolicy) -> (usize, usize) {
+    let files: Vec<(String, String)> = state
+        .file_index
+        .iter()
+        .map(|e| (e.key().clone(), e.value().clone()))
+        .collect();
+    let now = SystemTime::now();
+    let (mut compressed, mut deleted) = (0, 0);
+
+    for (session_id, file_path) in files {
+        // Leave running sessions alone whatever their age
+        if state.session_statuses.contains_key(&session_id) || state.session_panes.contains_key(&session_id) {
+            continue;
+        }
+        let age = match fs::metadata(&file_path).await.and_then(|m| m.modified()) {
+            Ok(modified) => now.duration_since(modified).unwrap_or_default(),
+            Err(_) => continue,
+        };
+
+        if policy.delete_after.is_some_and(|after| age >= after) {
+            // Through the trash, so the session's history.jsonl lines go too
+            if !storage::delete_session(state, &session_id).await {
+                eprintln!("[retention] failed to delete {}", session_id);
+                continue;
+            }
+            match trash::purge_session(state, &session_id).await {
+                Ok(()) => deleted += 1,
+                Err(e) => eprintln!("[retention] {}", e),
+            }
+        } else if policy.compress_after.is_some_and(|after| age >= after) && !is_compressed(&file_path) {
+            match compress_session(state, &session_id, &file_path, policy.codec).await {
+                Ok(()) => compressed += 1,
+                Err(e) => eprintln!("[retention] {}", e),
+            }
+        }
+    }
+    (compressed, deleted)
+}
+
+/// Compress and delete old transcripts, once now and then every hour.
+pub fn spawn_retention(state: Arc<AppState>, policy: RetentionPolicy) {
+    tokio::spawn(async move {
+        loop {
+            let (compressed, deleted) = apply_policy(&state, &policy).await;
+            if compressed > 0 || deleted > 0 {
+                eprintln!("[retention] compressed {} and deleted {} transcripts", compressed, deleted);
+                let _ = state.history_tx.send(());
+            }
+            tokio::time::sleep(RETENTION_INTERVAL).await;
+        }
+    });
+}
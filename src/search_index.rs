@@ -0,0 +1,550 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use tokio::fs;
+use tokio::sync::Notify;
+
+use crate::compression::{is_compressed, session_dir};
+use crate::models::ConversationMessage;
+use crate::state::AppState;
+use crate::storage::{extract_message_text, head_fingerprint, read_lines_from};
+
+/// Bump whenever the on-disk layout or the tokenizer changes — older indexes are rebuilt.
+const INDEX_VERSION: u32 = 3;
+const INDEX_MAGIC: &[u8; 4] = b"CRIX";
+const INDEX_END: &[u8; 4] = b"XIRC";
+/// Bytes hashed at the start of each file to detect a transcript being replaced.
+const HEAD_FINGERPRINT_LEN: u64 = 256;
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Index state for one session file.
+struct IndexedFile {
+    path: String,
+    /// Byte offset up to which the file has been indexed
+    offset: u64,
+    head_len: u64,
+    head_hash: u64,
+    /// Number of user/assistant messages seen so far (next message_index)
+    message_count: usize,
+    docs: Vec<u32>,
+}
+
+/// One indexed user/assistant message.
+struct IndexedDoc {
+    session_id: Arc<str>,
+    offset: u64,
+    message_index: usize,
+    uuid: Option<String>,
+}
+
+#[derive(Default)]
+struct IndexData {
+    files: HashMap<String, IndexedFile>,
+    docs: HashMap<u32, IndexedDoc>,
+    // trigram → sorted doc ids
+    terms: BTreeMap<String, Vec<u32>>,
+    next_doc: u32,
+}
+
+/// A candidate message returned by the index. Callers re-read the line at `offset`
+/// to confirm the match and build a snippet.
+#[derive(Debug, Clone)]
+pub struct IndexHit {
+    pub session_id: String,
+    pub offset: u64,
+    pub message_index: usize,
+}
+
+/// Persistent inverted index over user/assistant message text, stored in
+/// `<claude_dir>/search-index/`. Terms are trigrams of the lowercased text, so
+/// every message containing a query substring is a candidate.
+pub struct SearchIndex {
+    data: RwLock<IndexData>,
+    // sessionId → file path, waiting for the indexer task
+    pending: DashMap<String, String>,
+    notify: Notify,
+    dirty: std::sync::atomic::AtomicBool,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self {
+            data: RwLock::new(IndexData::default()),
+            pending: DashMap::new(),
+            notify: Notify::new(),
+            dirty: std::sync::atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// Queue a session file for (incremental) indexing.
+    pub fn schedule(&self, session_id: &str, file_path: &str) {
+        self.pending.insert(session_id.to_string(), file_path.to_string());
+        self.notify.notify_one();
+    }
+
+    /// Byte offset of the message with this uuid, if the session is indexed.
+    pub fn locate(&self, session_id: &str, uuid: &str) -> Option<u64> {
+        let data = self.data.read().unwrap();
+        let file = data.files.get(session_id)?;
+        file.docs
+            .iter()
+            .filter_map(|id| data.docs.get(id))
+            .find(|doc| doc.uuid.as_deref() == Some(uuid))
+            .map(|doc| doc.offset)
+    }
+
+    /// True when the index covers the whole file, so a session without candidates
+    /// has no matches. Compressed transcripts are never appended to.
+    pub async fn is_current(&self, session_id: &str, file_path: &str) -> bool {
+        let (path, offset) = match self.data.read().unwrap().files.get(session_id) {
+            Some(f) => (f.path.clone(), f.offset),
+            None => return false,
+        };
+        if is_compressed(file_path) {
+            return session_dir(&path) == session_dir(file_path);
+        }
+        path == file_path && fs::metadata(file_path).await.is_ok_and(|m| m.len() == offset)
+    }
+
+    /// Candidate messages containing every trigram of every (lowercased) query term:
+    /// a superset of the messages where each term is a substring. Returns `None`
+    /// when no term is long enough to narrow the search.
+    pub fn candidates(&self, terms: &[&str]) -> Option<Vec<IndexHit>> {
+        let grams: HashSet<String> = terms.iter().flat_map(|t| trigrams(t)).collect();
+        if grams.is_empty() {
+            return None;
+        }
+
+        let data = self.data.read().unwrap();
+        let mut sets: Vec<HashSet<u32>> = grams
+            .iter()
+            .map(|gram| {
+                data.terms
+                    .get(gram)
+                    .map(|docs| docs.iter().copied().collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+        sets.sort_by_key(|s| s.len());
+
+        let mut iter = sets.into_iter();
+        let mut result = iter.next().unwrap_or_default();
+        for set in iter {
+            result.retain(|d| set.contains(d));
+        }
+
+        let mut hits: Vec<IndexHit> = result
+            .into_iter()
+            .filter_map(|id| data.docs.get(&id))
+            .map(|doc| IndexHit {
+                session_id: doc.session_id.to_string(),
+                offset: doc.offset,
+                message_index: doc.message_index,
+            })
+            .collect();
+        hits.sort_by(|a, b| a.session_id.cmp(&b.session_id).then(a.offset.cmp(&b.offset)));
+        Some(hits)
+    }
+
+    fn remove_file(data: &mut IndexData, session_id: &str) {
+        let Some(file) = data.files.remove(session_id) else {
+            return;
+        };
+        let ids: HashSet<u32> = file.docs.iter().copied().collect();
+        for id in &ids {
+            data.docs.remove(id);
+        }
+        data.terms.retain(|_, docs| {
+            docs.retain(|d| !ids.contains(d));
+            !docs.is_empty()
+        });
+    }
+}
+
+/// Distinct character trigrams of the lowercased text.
+fn trigrams(text: &str) -> HashSet<String> {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    chars.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+/// Index bytes appended to a session file since the last run. Truncated or
+/// replaced files are dropped and indexed again from the start.
+pub(crate) async fn update_file(index: &SearchIndex, session_id: &str, file_path: &str) {
+    let file_size = match fs::metadata(file_path).await {
+        Ok(m) => m.len(),
+        Err(_) => {
+            let mut data = index.data.write().unwrap();
+            if data.files.contains_key(session_id) {
+                SearchIndex::remove_file(&mut data, session_id);
+                index.dirty.store(true, std::sync::atomic::Ordering::Release);
+            }
+            return;
+        }
+    };
+
+    // Decide whether the existing entry is still valid
+    let known = {
+        let data = index.data.read().unwrap();
+        data.files
+            .get(session_id)
+            .map(|f| (f.path.clone(), f.offset, f.head_len, f.head_hash))
+    };
+    let mut from_offset = 0;
+    if let Some((path, offset, head_len, head_hash)) = known {
+        // Compressed transcripts are never appended to and hold the same lines
+        // at the same offsets as the `.jsonl` they replaced
+        if is_compressed(file_path) && session_dir(&path) == session_dir(file_path) {
+            if path != file_path {
+                let mut data = index.data.write().unwrap();
+                if let Some(f) = data.files.get_mut(session_id) {
+                    f.path = file_path.to_string();
+                }
+                index.dirty.store(true, std::sync::atomic::Ordering::Release);
+            }
+            return;
+        }
+        let same_head = path == file_path
+            && file_size >= offset
+            && head_fingerprint(file_path, head_len).await == Some(head_hash);
+        if same_head {
+            if file_size == offset {
+                return;
+            }
+            from_offset = offset;
+        } else {
+            let mut data = index.data.write().unwrap();
+            SearchIndex::remove_file(&mut data, session_id);
+        }
+    }
+
+    let (lines, next_offset) = read_lines_from(file_path, from_offset).await;
+
+    // Tokenize outside the lock
+    let mut parsed: Vec<(u64, Option<String>, HashSet<String>)> = Vec::with_capacity(lines.len());
+    for (offset, line) in &lines {
+        let msg: ConversationMessage = match serde_json::from_str(line) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        if msg.msg_type != "user" && msg.msg_type != "assistant" {
+            continue;
+        }
+        let terms = trigrams(&extract_message_text(&msg));
+        parsed.push((*offset, msg.uuid, terms));
+    }
+
+    let head = if from_offset == 0 {
+        let head_len = HEAD_FINGERPRINT_LEN.min(next_offset);
+        head_fingerprint(file_path, head_len).await.map(|h| (head_len, h))
+    } else {
+        None
+    };
+
+    let mut data = index.data.write().unwrap();
+    let data = &mut *data;
+    if data.files.get(session_id).map(|f| f.offset).unwrap_or(0) != from_offset {
+        // The entry changed while we were reading; the next change will catch up
+        return;
+    }
+    let session_key: Arc<str> = Arc::from(session_id);
+    let file = data.files.entry(session_id.to_string()).or_insert_with(|| IndexedFile {
+        path: file_path.to_string(),
+        offset: 0,
+        head_len: 0,
+        head_hash: 0,
+        message_count: 0,
+        docs: Vec::new(),
+    });
+    if let Some((head_len, head_hash)) = head {
+        file.head_len = head_len;
+        file.head_hash = head_hash;
+    }
+
+    // Every message gets a doc (even without terms) so uuids can be located
+    for (offset, uuid, terms) in parsed {
+        let message_index = file.message_count;
+        file.message_count += 1;
+
+        let id = data.next_doc;
+        data.next_doc += 1;
+        data.docs.insert(
+            id,
+            IndexedDoc {
+                session_id: session_key.clone(),
+                offset,
+                message_index,
+                uuid,
+            },
+        );
+        file.docs.push(id);
+        for term in terms {
+            data.terms.entry(term).or_default().push(id);
+        }
+    }
+    file.offset = next_offset;
+    index.dirty.store(true, std::sync::atomic::Ordering::Release);
+}
+
+// --- Persistence ---
+
+fn index_dir(state: &AppState) -> String {
+    format!("{}/search-index", state.claude_dir)
+}
+
+fn index_path(state: &AppState) -> String {
+    format!("{}/index.bin", index_dir(state))
+}
+
+fn put_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, v: u64) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_u32(buf, s.len() as u32);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n)?;
+        let slice = self.buf.get(self.pos..end)?;
+        self.pos = end;
+        Some(slice)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn varint(&mut self) -> Option<u32> {
+        let mut v: u32 = 0;
+        for shift in (0..35).step_by(7) {
+            let b = *self.take(1)?.first()?;
+            v |= ((b & 0x7f) as u32) << shift;
+            if b & 0x80 == 0 {
+                return Some(v);
+            }
+        }
+        None
+    }
+}
+
+fn encode(data: &IndexData) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(INDEX_MAGIC);
+    put_u32(&mut buf, INDEX_VERSION);
+    put_u32(&mut buf, data.next_doc);
+
+    let sessions: Vec<&String> = data.files.keys().collect();
+    let positions: HashMap<&str, u32> = sessions
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.as_str(), i as u32))
+        .collect();
+
+    put_u32(&mut buf, sessions.len() as u32);
+    for session_id in &sessions {
+        let file = &data.files[*session_id];
+        put_str(&mut buf, session_id);
+        put_str(&mut buf, &file.path);
+        put_u64(&mut buf, file.offset);
+        put_u64(&mut buf, file.head_len);
+        put_u64(&mut buf, file.head_hash);
+        put_u64(&mut buf, file.message_count as u64);
+    }
+
+    put_u32(&mut buf, data.docs.len() as u32);
+    for (id, doc) in &data.docs {
+        put_u32(&mut buf, *id);
+        put_u32(&mut buf, positions[&*doc.session_id]);
+        put_u64(&mut buf, doc.offset);
+        put_u64(&mut buf, doc.message_index as u64);
+        put_str(&mut buf, doc.uuid.as_deref().unwrap_or(""));
+    }
+
+    put_u32(&mut buf, data.terms.len() as u32);
+    for (term, docs) in &data.terms {
+        put_str(&mut buf, term);
+        put_u32(&mut buf, docs.len() as u32);
+        let mut prev = 0;
+        for id in docs {
+            put_varint(&mut buf, id - prev);
+            prev = *id;
+        }
+    }
+
+    buf.extend_from_slice(INDEX_END);
+    buf
+}
+
+fn decode(buf: &[u8]) -> Option<IndexData> {
+    let mut r = Reader { buf, pos: 0 };
+    if r.take(4)? != INDEX_MAGIC || r.u32()? != INDEX_VERSION {
+        return None;
+    }
+    let mut data = IndexData {
+        next_doc: r.u32()?,
+        ..Default::default()
+    };
+
+    let file_count = r.u32()?;
+    let mut sessions: Vec<Arc<str>> = Vec::new();
+    for _ in 0..file_count {
+        let session_id = r.string()?;
+        let file = IndexedFile {
+            path: r.string()?,
+            offset: r.u64()?,
+            head_len: r.u64()?,
+            head_hash: r.u64()?,
+            message_count: r.u64()? as usize,
+            docs: Vec::new(),
+        };
+        sessions.push(Arc::from(session_id.as_str()));
+        data.files.insert(session_id, file);
+    }
+
+    let doc_count = r.u32()?;
+    for _ in 0..doc_count {
+        let id = r.u32()?;
+        let session_id = sessions.get(r.u32()? as usize)?.clone();
+        let offset = r.u64()?;
+        let message_index = r.u64()? as usize;
+        let uuid = Some(r.string()?).filter(|u| !u.is_empty());
+        if id >= data.next_doc {
+            return None;
+        }
+        data.files.get_mut(&*session_id)?.docs.push(id);
+        data.docs.insert(id, IndexedDoc { session_id, offset, message_index, uuid });
+    }
+    for file in data.files.values_mut() {
+        file.docs.sort_unstable();
+    }
+
+    let term_count = r.u32()?;
+    for _ in 0..term_count {
+        let term = r.string()?;
+        let len = r.u32()? as usize;
+        let mut docs = Vec::with_capacity(len.min(buf.len()));
+        let mut prev: u32 = 0;
+        for _ in 0..len {
+            prev = prev.checked_add(r.varint()?)?;
+            if !data.docs.contains_key(&prev) {
+                return None;
+            }
+            docs.push(prev);
+        }
+        data.terms.insert(term, docs);
+    }
+
+    if r.take(4)? != INDEX_END || r.pos != buf.len() {
+        return None;
+    }
+    Some(data)
+}
+
+/// Load the persisted index. A missing, corrupt or outdated index starts empty
+/// and is rebuilt by the indexer task.
+pub async fn load_index(state: &AppState) {
+    let path = index_path(state);
+    let buf = match fs::read(&path).await {
+        Ok(b) => b,
+        Err(_) => return,
+    };
+    match decode(&buf) {
+        Some(data) => {
+            eprintln!(
+                "[search-index] loaded {} sessions, {} terms",
+                data.files.len(),
+                data.terms.len()
+            );
+            *state.search_index.data.write().unwrap() = data;
+        }
+        None => {
+            eprintln!("[search-index] index is corrupt or outdated, rebuilding");
+            let _ = fs::remove_file(&path).await;
+        }
+    }
+}
+
+/// Persist the index if it changed since the last save (write to a temp file, then rename).
+pub async fn save_index(state: &AppState) {
+    if !state.search_index.dirty.swap(false, std::sync::atomic::Ordering::AcqRel) {
+        return;
+    }
+    let buf = encode(&state.search_index.data.read().unwrap());
+    let dir = index_dir(state);
+    let _ = fs::create_dir_all(&dir).await;
+    let tmp_path = format!("{}/index.bin.tmp", dir);
+    if fs::write(&tmp_path, &buf).await.is_err() || fs::rename(&tmp_path, index_path(state)).await.is_err() {
+        eprintln!("[search-index] failed to save index");
+        state.search_index.dirty.store(true, std::sync::atomic::Ordering::Release);
+    }
+}
+
+/// Background task that indexes queued session files and periodically saves the index.
+pub fn spawn_indexer(state: Arc<AppState>) {
+    // Catch up with files that changed while we were not running, and drop
+    // sessions whose files are gone
+    for entry in state.file_index.iter() {
+        state.search_index.schedule(entry.key(), entry.value());
+    }
+    let known: Vec<(String, String)> = {
+        let data = state.search_index.data.read().unwrap();
+        data.files.iter().map(|(k, f)| (k.clone(), f.path.clone())).collect()
+    };
+    for (session_id, path) in known {
+        if !state.file_index.contains_key(&session_id) {
+            state.search_index.schedule(&session_id, &path);
+        }
+    }
+
+    tokio::spawn(async move {
+        let mut last_save = Instant::now();
+        loop {
+            tokio::select! {
+                _ = state.search_index.notify.notified() => {}
+                _ = tokio::time::sleep(SAVE_INTERVAL) => {}
+            }
+
+            let batch: Vec<String> = state.search_index.pending.iter().map(|e| e.key().clone()).collect();
+            for session_id in batch {
+                if let Some((session_id, path)) = state.search_index.pending.remove(&session_id) {
+                    update_file(&state.search_index, &session_id, &path).await;
+                }
+            }
+
+            if last_save.elapsed() >= SAVE_INTERVAL {
+                save_index(&state).await;
+                last_save = Instant::now();
+            }
+        }
+    });
+}
@@ -0,0 +1,307 @@
+use regex::Regex;
+
+/// A single condition in a search query.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// Case-insensitive substring (plain word or quoted phrase), stored lowercased
+    Text(String),
+    Regex(Regex),
+    /// Substring of the session's project path
+    Project(String),
+    /// Exact git branch recorded on the message
+    Branch(String),
+    Role(String),
+    /// Assistant message calling this tool
+    Tool(String),
+    /// Substring of the assistant model name
+    Model(String),
+    /// Message timestamp strictly before this date (YYYY-MM-DD)
+    Before(String),
+    /// Message timestamp strictly after this date (YYYY-MM-DD)
+    After(String),
+    /// Message carries a `tool_result` with `is_error: true`
+    IsError,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryTerm {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+/// Parsed search query: every term must hold (AND).
+///
+/// Syntax: plain words, `"quoted phrases"`, `/regex/`, `-` to negate any term, and
+/// `project:`, `branch:`, `role:user|assistant`, `tool:`, `model:`,
+/// `before:YYYY-MM-DD`, `after:YYYY-MM-DD`, `is:error` filters.
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    pub terms: Vec<QueryTerm>,
+}
+
+impl SearchQuery {
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Positive text terms, used to narrow candidates through the search index.
+    pub fn index_terms(&self) -> Vec<&str> {
+        self.terms
+            .iter()
+            .filter(|t| !t.negated)
+            .filter_map(|t| match &t.filter {
+                Filter::Text(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The term used to centre snippets: the first positive text or regex term.
+    pub fn highlight(&self) -> Option<&Filter> {
+        self.terms
+            .iter()
+            .filter(|t| !t.negated)
+            .map(|t| &t.filter)
+            .find(|f| matches!(f, Filter::Text(_) | Filter::Regex(_)))
+    }
+}
+
+/// Split the input into raw tokens, keeping quoted sections and `/regex/` bodies together.
+fn split_tokens(input: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut token = String::new();
+        // A regex starts after an optional `-`
+        let mut regex = false;
+        if c == '-' {
+            token.push(c);
+            chars.next();
+        }
+        if chars.peek() == Some(&'/') {
+            regex = true;
+        }
+
+        if regex {
+            token.push(chars.next().unwrap());
+            let mut closed = false;
+            while let Some(c) = chars.next() {
+                token.push(c);
+                if c == '\\' {
+                    if let Some(escaped) = chars.next() {
+                        token.push(escaped);
+                    }
+                } else if c == '/' {
+                    closed = true;
+                    break;
+                }
+            }
+            if !closed {
+                return Err(format!("Unterminated regex: {}", token));
+            }
+            if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                return Err(format!("Unexpected characters after regex: {}", token));
+            }
+        } else {
+            let mut in_quotes = false;
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() && !in_quotes {
+                    break;
+                }
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                }
+                token.push(c);
+                chars.next();
+            }
+            if in_quotes {
+                return Err(format!("Unterminated quote: {}", token));
+            }
+        }
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn unquote(value: &str) -> String {
+    value.trim_matches('"').to_string()
+}
+
+fn parse_date(field: &str, value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let valid = bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 4 || i == 7 || b.is_ascii_digit())
+        && matches!(value[5..7].parse::<u32>(), Ok(1..=12))
+        && matches!(value[8..10].parse::<u32>(), Ok(1..=31));
+    if valid {
+        Ok(value.to_string())
+    } else {
+        Err(format!("Invalid date for {}: '{}' (expected YYYY-MM-DD)", field, value))
+    }
+}
+
+fn parse_term(token: &str) -> Result<QueryTerm, String> {
+    let (negated, body) = match token.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+
+    if body.len() >= 2 && body.starts_with('/') && body.ends_with('/') {
+        let pattern = &body[1..body.len() - 1];
+        if pattern.is_empty() {
+            return Err("Empty regex".to_string());
+        }
+        let regex = Regex::new(&format!("(?i){}", pattern))
+            .map_err(|e| format!("Invalid regex /{}/: {}", pattern, e))?;
+        return Ok(QueryTerm { negated, filter: Filter::Regex(regex) });
+    }
+
+    if !body.starts_with('"') {
+        if let Some((field, value)) = body.split_once(':') {
+            let value = unquote(value);
+            let known = matches!(
+                field.to_lowercase().as_str(),
+                "project" | "branch" | "role" | "tool" | "model" | "before" | "after" | "is"
+            );
+            if known && value.is_empty() {
+                return Err(format!("Missing value for {}:", field));
+            }
+            let filter = match field.to_lowercase().as_str() {
+                "project" => Some(Filter::Project(value.to_lowercase())),
+                "branch" => Some(Filter::Branch(value)),
+                "role" => match value.to_lowercase().as_str() {
+                    role @ ("user" | "assistant") => Some(Filter::Role(role.to_string())),
+                    _ => return Err(format!("Invalid role: '{}' (expected user or assistant)", value)),
+                },
+                "tool" => Some(Filter::Tool(value.to_lowercase())),
+                "model" => Some(Filter::Model(value.to_lowercase())),
+                "before" => Some(Filter::Before(parse_date("before", &value)?)),
+                "after" => Some(Filter::After(parse_date("after", &value)?)),
+                "is" => match value.to_lowercase().as_str() {
+                    "error" => Some(Filter::IsError),
+                    _ => return Err(format!("Unknown is: value '{}' (expected error)", value)),
+                },
+                // Not a known field (e.g. a URL or `foo:bar`): treat as text
+                _ => None,
+            };
+            if let Some(filter) = filter {
+                return Ok(QueryTerm { negated, filter });
+            }
+        }
+    }
+
+    let text = unquote(body);
+    if text.is_empty() {
+        return Err("Empty search term".to_string());
+    }
+    Ok(QueryTerm { negated, filter: Filter::Text(text.to_lowercase()) })
+}
+
+pub fn parse(input: &str) -> Result<SearchQuery, String> {
+    let terms = split_tokens(input)?
+        .iter()
+        .map(|t| parse_term(t))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(SearchQuery { terms })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(input: &str) -> Vec<(bool, Filter)> {
+        parse(input).unwrap().terms.into_iter().map(|t| (t.negated, t.filter)).collect()
+    }
+
+    fn error(input: &str) -> String {
+        parse(input).unwrap_err()
+    }
+
+    #[test]
+    fn parses_words_phrases_and_negation() {
+        let terms = filters(r#"Foo "Bar Baz" -qux"#);
+        assert!(matches!(&terms[0], (false, Filter::Text(s)) if s == "foo"));
+        assert!(matches!(&terms[1], (false, Filter::Text(s)) if s == "bar baz"));
+        assert!(matches!(&terms[2], (true, Filter::Text(s)) if s == "qux"));
+        assert_eq!(parse("  ").unwrap().terms.len(), 0);
+        // A lone dash is a word, not a negation
+        assert!(matches!(&filters("-")[0], (false, Filter::Text(s)) if s == "-"));
+    }
+
+    #[test]
+    fn parses_regexes() {
+        let terms = filters(r"/fo+\/bar/ -/^x/");
+        let (false, Filter::Regex(re)) = &terms[0] else { panic!("expected regex") };
+        assert!(re.is_match("FOOO/BAR"));
+        assert!(matches!(&terms[1], (true, Filter::Regex(_))));
+    }
+
+    #[test]
+    fn parses_filters() {
+        let terms = filters(
+            r#"project:"My App" branch:Feat/X role:User tool:Bash model:Opus before:2024-01-31 after:2023-12-01 -is:error"#,
+        );
+        assert!(matches!(&terms[0], (false, Filter::Project(s)) if s == "my app"));
+        assert!(matches!(&terms[1], (false, Filter::Branch(s)) if s == "Feat/X"));
+        assert!(matches!(&terms[2], (false, Filter::Role(s)) if s == "user"));
+        assert!(matches!(&terms[3], (false, Filter::Tool(s)) if s == "bash"));
+        assert!(matches!(&terms[4], (false, Filter::Model(s)) if s == "opus"));
+        assert!(matches!(&terms[5], (false, Filter::Before(s)) if s == "2024-01-31"));
+        assert!(matches!(&terms[6], (false, Filter::After(s)) if s == "2023-12-01"));
+        assert!(matches!(&terms[7], (true, Filter::IsError)));
+    }
+
+    #[test]
+    fn unknown_fields_and_quoted_colons_are_text() {
+        let terms = filters(r#"https://example.com foo:bar "role:user""#);
+        assert!(matches!(&terms[0], (false, Filter::Text(s)) if s == "https://example.com"));
+        assert!(matches!(&terms[1], (false, Filter::Text(s)) if s == "foo:bar"));
+        assert!(matches!(&terms[2], (false, Filter::Text(s)) if s == "role:user"));
+    }
+
+    #[test]
+    fn index_terms_and_highlight() {
+        let query = parse(r#"/re/ foo -bar role:user "baz qux""#).unwrap();
+        assert_eq!(query.index_terms(), vec!["foo", "baz qux"]);
+        assert!(matches!(query.highlight(), Some(Filter::Regex(_))));
+        assert!(parse("-foo").unwrap().highlight().is_none());
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        assert_eq!(error("/abc"), "Unterminated regex: /abc");
+        assert_eq!(error("/abc/def"), "Unexpected characters after regex: /abc/");
+        assert_eq!(error(r#"foo "bar baz"#), r#"Unterminated quote: "bar baz"#);
+        assert_eq!(error("//"), "Empty regex");
+        assert!(error("/(/").starts_with("Invalid regex /(/:"));
+        assert_eq!(error(r#""""#), "Empty search term");
+    }
+
+    #[test]
+    fn reports_invalid_filter_values() {
+        assert_eq!(error("branch:"), "Missing value for branch:");
+        assert_eq!(error(r#"project:"""#), "Missing value for project:");
+        assert_eq!(error("role:system"), "Invalid role: 'system' (expected user or assistant)");
+        assert_eq!(error("is:open"), "Unknown is: value 'open' (expected error)");
+        for date in ["2024-1-01", "2024-13-01", "2024-01-32", "2024/01/01", "yesterday"] {
+            assert_eq!(
+                error(&format!("before:{}", date)),
+                format!("Invalid date for before: '{}' (expected YYYY-MM-DD)", date)
+            );
+        }
+        assert!(error("after:2024-00-10").starts_with("Invalid date for after:"));
+    }
+}
@@ -1,12 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use axum::{
-    extract::{Path, Query, State},
-    http::Method,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{HeaderMap, Method},
+    middleware,
     response::{
         sse::{Event, KeepAlive, Sse},
         IntoResponse, Json,
@@ -19,11 +21,26 @@ use tokio::io::AsyncReadExt;
 use tokio_stream::Stream;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 
+use crate::agent_blame;
+use crate::analytics::{self, AnalyticsFilter, GroupBy};
+use crate::bundle;
+use crate::changes;
+use crate::conversation_tree;
 use crate::embedded::serve_embedded;
+use crate::export::{self, ExportFormat, ExportOptions};
+use crate::federation;
+use crate::file_refs;
 use crate::models::*;
+use crate::plans::{self, PlanFilter};
 use crate::push;
-use crate::state::AppState;
+use crate::retention;
+use crate::search_query;
+use crate::state::{AppState, Source};
 use crate::storage;
+use crate::subagent_tree;
+use crate::tool_calls::{self, ToolCallTracker};
+use crate::trash;
+use crate::user_meta::{self, UserMetaUpdate};
 
 /// Build a `zellij` Command with optional `--session` argument.
 fn zellij_cmd(session: Option<&str>) -> tokio::process::Command {
@@ -34,6 +51,15 @@ fn zellij_cmd(session: Option<&str>) -> tokio::process::Command {
     cmd
 }
 
+/// `CLAUDE_CONFIG_DIR=... ` prefix so a launched claude writes into `source`.
+/// Empty for the default `~/.claude`.
+fn config_dir_env(source: &Source) -> String {
+    if source.is_default_home() {
+        return String::new();
+    }
+    format!("CLAUDE_CONFIG_DIR='{}' ", source.claude_dir.replace('\'', "'\\''"))
+}
+
 /// Ensure a Zellij session exists, creating it via Python PTY if needed.
 async fn ensure_zellij_session(name: &str) -> Result<(), String> {
     // Check if session already exists
@@ -116,21 +142,46 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/api/sessions/:id/answer", post(answer_question))
         .route("/api/sessions/stream", get(sessions_stream))
         .route("/api/projects", get(get_projects))
+        .route("/api/sources", get(get_sources))
         .route("/api/search", post(search))
+        .route("/api/search/stream", get(search_stream))
         .route("/api/conversation/:id", get(get_conversation))
         .route("/api/conversation/:id/stream", get(conversation_stream))
         .route("/api/conversation/:id/tail", get(conversation_tail))
         .route("/api/conversation/:id/older", get(conversation_older))
+        .route("/api/conversation/:id/around", get(conversation_around))
+        .route("/api/conversation/:id/tree", get(get_conversation_tree))
+        .route("/api/conversation/:id/tools", get(get_tool_calls))
+        .route("/api/conversation/:id/changes", get(get_session_changes))
+        .route("/api/conversation/:id/export", get(export_conversation))
+        .route("/api/conversation/:id/context-timeline", get(get_context_timeline))
+        .route("/api/conversation/:id/context-timeline/stream", get(context_timeline_stream))
         .route("/api/conversation/:id/subagents", get(get_subagents))
+        .route("/api/conversation/:id/subagents/tree", get(get_subagent_tree))
         .route(
             "/api/conversation/:id/subagent/:agent_id",
             get(get_subagent_conversation),
         )
+        .route("/api/conversation/:id/subagents/:agent_id/stream", get(subagent_stream))
+        .route("/api/conversation/:id/subagents/:agent_id/tail", get(subagent_tail))
+        .route("/api/conversation/:id/subagents/:agent_id/older", get(subagent_older))
         .route("/api/conversation/:id/plan-sessions", get(get_plan_sessions))
+        .route("/api/plans", get(get_plans))
         .route("/api/usage", get(get_usage))
+        .route("/api/analytics", get(get_analytics))
         .route("/api/launch", post(launch_agent))
         .route("/api/sessions/:id/resurrect", post(resurrect_session))
         .route("/api/sessions/:id/kill", post(kill_session))
+        .route("/api/sessions/:id/bundle", get(get_session_bundle))
+        .route("/api/sessions/:id/restore", post(restore_session))
+        .route("/api/sessions/:id/meta", get(get_user_meta).patch(update_user_meta))
+        .route("/api/tags", get(get_tags))
+        .route("/api/trash", get(get_trash))
+        .route("/api/trash/:id", delete(purge_session))
+        .route(
+            "/api/import",
+            post(import_bundle).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
         .route("/api/zellij/sessions", get(get_zellij_sessions).post(create_zellij_session))
         .route("/api/tail", get(tail_file))
         .route("/api/tasks/:id/alive", get(check_task_alive))
@@ -142,12 +193,16 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/api/tts", post(crate::tts::tts_handler))
         .route("/api/file", get(get_file))
         .route("/api/files", get(get_files))
+        .route("/api/files/sessions", get(get_file_sessions))
         .route("/api/git/diff", get(get_git_diff))
         .route("/api/git/changed-files", get(get_git_changed_files))
+        .route("/api/git/agent-blame", get(get_agent_blame))
         .route("/api/client-error", post(client_error))
 ;
 
-    let mut router = api;
+    let mut router = api
+        .layer(middleware::from_fn_with_state(state.clone(), federation::proxy_peer_requests))
+        .layer(middleware::from_fn_with_state(state.clone(), federation::require_token));
 
     if state.dev_mode {
         let cors = CorsLayer::new()
@@ -167,9 +222,63 @@ pub fn create_router(state: Arc<AppState>) -> Router {
 
 // --- REST Handlers ---
 
-async fn get_sessions(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let sessions = storage::get_sessions(&state).await;
-    Json(sessions)
+#[derive(Deserialize)]
+struct SessionsQuery {
+    /// Only sessions with this user tag
+    tag: Option<String>,
+}
+
+async fn get_sessions(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<SessionsQuery>,
+) -> impl IntoResponse {
+    let local_only = federation::is_peer_request(&headers);
+    Json(list_sessions(&state, query.tag.as_deref(), local_only).await)
+}
+
+/// Local sessions, plus those of peers unless `local_only` (the request comes
+/// from a peer).
+async fn list_sessions(state: &AppState, tag: Option<&str>, local_only: bool) -> Vec<Session> {
+    let sessions = storage::get_sessions(state, tag).await;
+    if local_only {
+        sessions
+    } else {
+        federation::merge_sessions(state, sessions, tag)
+    }
+}
+
+async fn get_user_meta(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    Json(user_meta::get_user_meta(&state, &id))
+}
+
+async fn update_user_meta(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<UserMetaUpdate>,
+) -> impl IntoResponse {
+    if storage::find_session_file(&state, &id).await.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response();
+    }
+    match user_meta::update_user_meta(&state, &id, body).await {
+        Ok(meta) => Json(meta).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e }))).into_response(),
+    }
+}
+
+async fn get_tags(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let tags: Vec<serde_json::Value> = user_meta::list_tags(&state)
+        .into_iter()
+        .map(|(tag, count)| serde_json::json!({ "tag": tag, "count": count }))
+        .collect();
+    Json(tags)
 }
 
 async fn get_projects(State(state): State<Arc<AppState>>) -> impl IntoResponse {
@@ -177,14 +286,121 @@ async fn get_projects(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     Json(projects)
 }
 
+async fn get_sources(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let sources: Vec<SourceInfo> = state
+        .sources
+        .iter()
+        .map(|s| SourceInfo {
+            label: s.label.clone(),
+            claude_dir: s.claude_dir.clone(),
+        })
+        .collect();
+    Json(sources)
+}
+
+#[derive(Deserialize)]
+struct ConversationQuery {
+    /// `active` to return only the branch leading to the active leaf
+    branch: Option<String>,
+}
+
 async fn get_conversation(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Query(query): Query<ConversationQuery>,
 ) -> impl IntoResponse {
-    let messages = storage::get_conversation(&state, &id).await;
+    let active_only = query.branch.as_deref() == Some("active");
+    let messages = storage::get_conversation(&state, &id, active_only).await;
     Json(messages)
 }
 
+async fn get_conversation_tree(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match conversation_tree::get_conversation_tree(&state, &id).await {
+        Some(tree) => Json(tree).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_tool_calls(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let calls = tool_calls::get_tool_calls(&state, &id).await;
+    Json(calls)
+}
+
+async fn get_session_changes(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    if storage::find_session_file(&state, &id).await.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response();
+    }
+    Json(changes::get_session_changes(&state, &id).await).into_response()
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportQuery {
+    /// md (default), html or json
+    format: Option<String>,
+    thinking: Option<bool>,
+    tool_results: Option<bool>,
+    subagents: Option<bool>,
+}
+
+async fn export_conversation(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> impl IntoResponse {
+    let format = match ExportFormat::parse(query.format.as_deref().unwrap_or("md")) {
+        Some(f) => f,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "Invalid format (expected md, html or json)" })),
+            )
+                .into_response()
+        }
+    };
+    let options = ExportOptions {
+        thinking: query.thinking.unwrap_or(false),
+        tool_results: query.tool_results.unwrap_or(true),
+        subagents: query.subagents.unwrap_or(false),
+    };
+
+    match export::build_export(&state, &id, options).await {
+        Some(doc) => (
+            [
+                (axum::http::header::CONTENT_TYPE, format.content_type().to_string()),
+                (
+                    axum::http::header::CONTENT_DISPOSITION,
+                    format!("inline; filename=\"{}.{}\"", id, format.extension()),
+                ),
+            ],
+            export::render(&doc, format),
+        )
+            .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
+}
+
 async fn get_subagents(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -193,6 +409,16 @@ async fn get_subagents(
     Json(infos)
 }
 
+async fn get_subagent_tree(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match subagent_tree::get_subagent_tree(&state, &id).await {
+        Some(tree) => Json(tree).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "Session not found" }))).into_response(),
+    }
+}
+
 async fn get_subagent_conversation(
     State(state): State<Arc<AppState>>,
     Path((id, agent_id)): Path<(String, String)>,
@@ -209,6 +435,25 @@ async fn get_plan_sessions(
     Json(infos)
 }
 
+#[derive(Deserialize)]
+struct PlansQuery {
+    session: Option<String>,
+    project: Option<String>,
+    q: Option<String>,
+}
+
+async fn get_plans(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PlansQuery>,
+) -> impl IntoResponse {
+    let filter = PlanFilter {
+        session: query.session,
+        project: query.project,
+        q: query.q,
+    };
+    Json(serde_json::json!({ "plans": plans::get_plans(&state, &filter).await }))
+}
+
 async fn delete_session(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -221,6 +466,95 @@ async fn delete_session(
     }
 }
 
+async fn get_trash(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(trash::list_trash(&state).await)
+}
+
+async fn restore_session(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    if !state.hidden_sessions.contains_key(&id) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not in trash" })),
+        )
+            .into_response();
+    }
+    match trash::restore_session(&state, &id).await {
+        Ok(()) => Json(serde_json::json!({ "success": true })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e }))).into_response(),
+    }
+}
+
+async fn purge_session(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    if !state.hidden_sessions.contains_key(&id) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not in trash" })),
+        )
+            .into_response();
+    }
+    match trash::purge_session(&state, &id).await {
+        Ok(()) => Json(serde_json::json!({ "success": true })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e }))).into_response(),
+    }
+}
+
+async fn get_session_bundle(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    if storage::find_session_file(&state, &id).await.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response();
+    }
+    match bundle::build_bundle(&state, &id).await {
+        Ok(data) => (
+            [
+                (axum::http::header::CONTENT_TYPE, "application/gzip".to_string()),
+                (
+                    axum::http::header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.tar.gz\"", id),
+                ),
+            ],
+            data,
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e })),
+        )
+            .into_response(),
+    }
+}
+
+/// Bundles carry whole transcripts, well past axum's 2 MB default.
+const IMPORT_BODY_LIMIT: usize = 512 * 1024 * 1024;
+
+#[derive(serde::Deserialize)]
+struct ImportQuery {
+    /// Project path to import into (defaults to the one recorded in the bundle)
+    project: Option<String>,
+}
+
+async fn import_bundle(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    match bundle::import_bundle(&state, &body, query.project.as_deref()).await {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response(),
+    }
+}
+
 async fn set_status(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -314,12 +648,9 @@ async fn set_status(
                 .summary_cache
                 .get(&id)
                 .map(|entry| entry.value().0.clone());
-            let project_name = state.file_index.get(&id).and_then(|entry| {
-                let path = std::path::Path::new(entry.value());
-                let dir_name = path.parent()?.file_name()?.to_str()?;
-                let decoded = storage::decode_project_path(dir_name);
-                Some(storage::get_project_name(&decoded))
-            });
+            let project_name = storage::get_session_project(&state, &id)
+                .await
+                .map(|p| storage::get_project_name(&p));
             tokio::spawn(async move {
                 // Desktop dashboard open → user is looking at it, skip
                 if desktop_active {
@@ -519,14 +850,103 @@ async fn answer_question(
 
 async fn search(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(body): Json<SearchRequest>,
 ) -> impl IntoResponse {
-    let query = body.query.trim().to_string();
+    let query = match search_query::parse(&body.query) {
+        Ok(q) => q,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response();
+        }
+    };
     if query.is_empty() {
-        return Json(serde_json::json!({ "results": [] }));
+        return Json(serde_json::json!({ "results": [] })).into_response();
+    }
+    let mut results = storage::search_conversations(&state.clone(), &query).await;
+    if !federation::is_peer_request(&headers) {
+        results.extend(federation::search_peers(&state, &body.query).await);
     }
-    let results = storage::search_conversations(&state.clone(), &query).await;
-    Json(serde_json::json!({ "results": results }))
+    Json(serde_json::json!({ "results": results })).into_response()
+}
+
+#[derive(Deserialize)]
+struct SearchStreamQuery {
+    q: String,
+}
+
+async fn search_stream(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<SearchStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<serde_json::Value>)> {
+    let query = search_query::parse(&params.q)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))))?;
+
+    // The run lives inside the stream: when the client disconnects, axum drops
+    // the stream and the outstanding session searches are aborted with it
+    let search_peers = !federation::is_peer_request(&headers) && !state.peers.is_empty();
+    let stream = async_stream::stream! {
+        let started = Instant::now();
+        // Peers search in parallel with the local run; their results follow it
+        let peer_search = search_peers.then(|| {
+            let state = state.clone();
+            let q = params.q.clone();
+            tokio::spawn(async move { federation::search_peers(&state, &q).await })
+        });
+        let mut run = storage::SearchRun::start(&state, &query).await;
+        while let Some(result) = run.next().await {
+            let data = serde_json::to_string(&result).unwrap_or_default();
+            yield Ok(Event::default().event("result").data(data));
+        }
+        if let Some(peer_search) = peer_search {
+            for result in peer_search.await.unwrap_or_default() {
+                let data = serde_json::to_string(&result).unwrap_or_default();
+                yield Ok(Event::default().event("result").data(data));
+            }
+        }
+
+        let mut done = serde_json::to_value(run.totals()).unwrap_or_default();
+        done["elapsedMs"] = serde_json::json!(started.elapsed().as_millis() as u64);
+        yield Ok(Event::default().event("done").data(done.to_string()));
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct AnalyticsQuery {
+    group: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    project: Option<String>,
+}
+
+async fn get_analytics(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AnalyticsQuery>,
+) -> impl IntoResponse {
+    let bad_request = |e: String| (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response();
+
+    let group = match query.group.as_deref().unwrap_or("day") {
+        "session" => GroupBy::Session,
+        "project" => GroupBy::Project,
+        "model" => GroupBy::Model,
+        "day" => GroupBy::Day,
+        other => return bad_request(format!("Invalid group: '{}' (expected session, project, model or day)", other)),
+    };
+    for date in [&query.from, &query.to].into_iter().flatten() {
+        if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            return bad_request(format!("Invalid date: '{}' (expected YYYY-MM-DD)", date));
+        }
+    }
+
+    let filter = AnalyticsFilter {
+        group,
+        from: query.from,
+        to: query.to,
+        project: query.project,
+    };
+    Json(analytics::get_analytics(&state, &filter).await).into_response()
 }
 
 async fn get_usage(State(state): State<Arc<AppState>>) -> impl IntoResponse {
@@ -634,7 +1054,7 @@ async fn open_url(
         return StatusCode::BAD_REQUEST;
     }
     // Try SSE broadcast first
-    let sse_ok = state.url_tx.send(url.to_string()).is_ok();
+    let _ = state.url_tx.send(url.to_string());
 
     // Also send push notification (works even if SSE is disconnected)
     let state2 = state.clone();
@@ -643,13 +1063,23 @@ async fn open_url(
         push::send_url_notification(&state2, &url2).await;
     });
 
-    if sse_ok { StatusCode::OK } else { StatusCode::OK }
+    StatusCode::OK
 }
 
 async fn launch_agent(
+    State(state): State<Arc<AppState>>,
     Json(body): Json<LaunchRequest>,
 ) -> impl IntoResponse {
-    eprintln!("[launch] project={:?} zellij_session={:?} skip={:?}", body.project, body.zellij_session, body.dangerously_skip_permissions);
+    eprintln!("[launch] project={:?} zellij_session={:?} skip={:?} source={:?}", body.project, body.zellij_session, body.dangerously_skip_permissions, body.source);
+
+    let source = match body.source {
+        Some(ref label) => match state.sources.iter().find(|s| &s.label == label) {
+            Some(source) => source,
+            None => return Json(serde_json::json!({ "error": format!("Unknown source: {}", label) })),
+        },
+        None => &state.sources[0],
+    };
+    let env = config_dir_env(source);
 
     // Ensure the Zellij session exists (create if needed)
     if let Some(ref session_name) = body.zellij_session {
@@ -673,9 +1103,9 @@ async fn launch_agent(
     // Shell-escape the prompt by replacing single quotes
     let escaped_prompt = prompt.replace('\'', "'\\''");
     let cmd = if body.dangerously_skip_permissions.unwrap_or(false) {
-        format!("$SHELL -c 'claude --dangerously-skip-permissions \"{}\"'", escaped_prompt)
+        format!("{}$SHELL -c 'claude --dangerously-skip-permissions \"{}\"'", env, escaped_prompt)
     } else {
-        format!("$SHELL -c 'claude \"{}\"'", escaped_prompt)
+        format!("{}$SHELL -c 'claude \"{}\"'", env, escaped_prompt)
     };
 
     args.extend(["--", "sh", "-c"]);
@@ -708,10 +1138,22 @@ async fn launch_agent(
 }
 
 async fn resurrect_session(
+    State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
     Json(body): Json<ResurrectRequest>,
 ) -> impl IntoResponse {
-    eprintln!("[resurrect] session={} project={} zellij_session={:?} skip={:?}", id, body.project, body.zellij_session, body.dangerously_skip_permissions);
+    // The transcript's cwd is authoritative; the client's project is a fallback
+    let project = storage::get_session_project(&state, &id)
+        .await
+        .filter(|p| std::path::Path::new(p).is_dir())
+        .unwrap_or_else(|| body.project.clone());
+    eprintln!("[resurrect] session={} project={} zellij_session={:?} skip={:?}", id, project, body.zellij_session, body.dangerously_skip_permissions);
+
+    // claude can't resume into a compressed transcript
+    if let Err(e) = retention::ensure_uncompressed(&state, &id).await {
+        eprintln!("[resurrect] {}", e);
+        return Json(serde_json::json!({ "error": e }));
+    }
 
     // Ensure the Zellij session exists (create if needed)
     if let Some(ref session_name) = body.zellij_session {
@@ -725,12 +1167,14 @@ async fn resurrect_session(
         eprintln!("[resurrect] WARNING: no zellij_session provided");
     }
 
-    let mut args = vec!["action", "new-tab", "--cwd", &body.project];
+    let mut args = vec!["action", "new-tab", "--cwd", &project];
 
+    // Resume with the config dir the session was recorded in
+    let env = config_dir_env(state.session_source(&id));
     let cmd = if body.dangerously_skip_permissions.unwrap_or(false) {
-        format!("$SHELL -c 'claude --resume {} --dangerously-skip-permissions'", id)
+        format!("{}$SHELL -c 'claude --resume {} --dangerously-skip-permissions'", env, id)
     } else {
-        format!("$SHELL -c 'claude --resume {}'", id)
+        format!("{}$SHELL -c 'claude --resume {}'", env, id)
     };
 
     args.extend(["--", "sh", "-c"]);
@@ -969,19 +1413,21 @@ async fn check_task_alive(Path(task_id): Path<String>) -> impl IntoResponse {
 
 async fn sessions_stream(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let local_only = federation::is_peer_request(&headers);
     let stream = async_stream::stream! {
 
-        let mut known_sessions: HashMap<String, (f64, SessionStatus)> = HashMap::new();
+        let mut known_sessions: HashMap<String, KnownSession> = HashMap::new();
         let mut history_rx = state.history_tx.subscribe();
         let mut session_rx = state.session_tx.subscribe();
         let mut status_rx = state.status_tx.subscribe();
         let mut url_rx = state.url_tx.subscribe();
 
         // Send initial sessions
-        let sessions = storage::get_sessions(&state).await;
+        let sessions = list_sessions(&state, None, local_only).await;
         for s in &sessions {
-            known_sessions.insert(s.id.clone(), (s.last_activity, s.status.clone()));
+            known_sessions.insert(s.id.clone(), known_session(&state, s));
         }
         let data = serde_json::to_string(&sessions).unwrap_or_default();
         yield Ok(Event::default().event("sessions").data(data));
@@ -990,14 +1436,14 @@ async fn sessions_stream(
             tokio::select! {
                 _ = history_rx.recv() => {
                     // History changed — push session updates
-                    if let Some(updates) = compute_session_updates(&state, &mut known_sessions).await {
-                        yield Ok(updates);
+                    for event in compute_session_updates(&state, &mut known_sessions, local_only).await {
+                        yield Ok(event);
                     }
                 }
                 _ = session_rx.recv() => {
                     // Session file changed — push session updates
-                    if let Some(updates) = compute_session_updates(&state, &mut known_sessions).await {
-                        yield Ok(updates);
+                    for event in compute_session_updates(&state, &mut known_sessions, local_only).await {
+                        yield Ok(event);
                     }
                 }
                 Ok((session_id, status)) = status_rx.recv() => {
@@ -1033,31 +1479,62 @@ async fn sessions_stream(
     Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
+/// (last activity, status, user metadata or peer revision) last sent for a session
+type KnownSession = (f64, SessionStatus, f64);
+
+fn known_session(state: &AppState, s: &Session) -> KnownSession {
+    let updated = match s.host {
+        Some(_) => federation::revision(state, &s.id),
+        None => state.user_meta.get(&s.id).map(|m| m.updated_at).unwrap_or(0.0),
+    };
+    (s.last_activity, s.status.clone(), updated)
+}
+
+/// `sessionsUpdate` for new or changed sessions and `sessionsRemoved` with the
+/// ids of sessions that left the list (deleted, trashed or purged).
 async fn compute_session_updates(
     state: &AppState,
-    known_sessions: &mut HashMap<String, (f64, SessionStatus)>,
-) -> Option<Event> {
-    let sessions = storage::get_sessions(state).await;
+    known_sessions: &mut HashMap<String, KnownSession>,
+    local_only: bool,
+) -> Vec<Event> {
+    let sessions = list_sessions(state, None, local_only).await;
+    let mut events = Vec::new();
     let mut new_or_updated = Vec::new();
 
+    let current_ids: HashSet<&str> = sessions.iter().map(|s| s.id.as_str()).collect();
+    let removed: Vec<String> = known_sessions
+        .keys()
+        .filter(|id| !current_ids.contains(id.as_str()))
+        .cloned()
+        .collect();
+    if !removed.is_empty() {
+        for id in &removed {
+            known_sessions.remove(id);
+        }
+        if state.dev_mode {
+            eprintln!("[sse] sending sessionsRemoved: {:?}", removed);
+        }
+        let data = serde_json::to_string(&removed).unwrap_or_default();
+        events.push(Event::default().event("sessionsRemoved").data(data));
+    }
+
     for s in &sessions {
-        let dominated = known_sessions.get(&s.id).is_none_or(|(la, st)| {
-            *la != s.last_activity || *st != s.status
-        });
+        let current = known_session(state, s);
+        let dominated = known_sessions.get(&s.id).is_none_or(|known| *known != current);
         if dominated {
             new_or_updated.push(s.clone());
         }
     }
 
     for s in &sessions {
-        known_sessions.insert(s.id.clone(), (s.last_activity, s.status.clone()));
+        known_sessions.insert(s.id.clone(), known_session(state, s));
     }
 
     if new_or_updated.is_empty() {
-        if state.dev_mode {
+        if state.dev_mode && events.is_empty() {
             eprintln!("[sse] compute_session_updates: no changes ({} sessions)", sessions.len());
         }
-        return None;
+        return events;
     }
 
     if state.dev_mode {
@@ -1065,12 +1542,15 @@ async fn compute_session_updates(
         eprintln!("[sse] sending sessionsUpdate: {} updates {:?}", new_or_updated.len(), ids);
     }
     let data = serde_json::to_string(&new_or_updated).unwrap_or_default();
-    Some(Event::default().event("sessionsUpdate").data(data))
+    events.push(Event::default().event("sessionsUpdate").data(data));
+    events
 }
 
 #[derive(Deserialize)]
 struct ConversationStreamQuery {
     offset: Option<u64>,
+    /// `active` to send only messages on the active branch (conversation stream only)
+    branch: Option<String>,
 }
 
 async fn conversation_stream(
@@ -1080,14 +1560,27 @@ async fn conversation_stream(
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     let session_id = id;
     let initial_offset = query.offset.unwrap_or(0);
+    let active_only = query.branch.as_deref() == Some("active");
 
     let stream = async_stream::stream! {
         let mut offset = initial_offset;
         let mut session_rx = state.session_tx.subscribe();
+        // Pairs tool calls seen on this stream; changes are sent as `tools` events
+        let mut tools = ToolCallTracker::new();
+        // With `branch=active`: uuids sent so far, to notice a rewind to another branch
+        let mut sent: HashSet<String> = HashSet::new();
 
         // Send initial messages
-        let result = storage::get_conversation_stream(&state, &session_id, offset).await;
+        let mut result = storage::get_conversation_stream(&state, &session_id, offset).await;
         offset = result.next_offset;
+        if active_only {
+            let branch = conversation_tree::get_active_branch(&state, &session_id).await;
+            result.messages.retain(|m| conversation_tree::on_branch(&branch, m));
+            sent.extend(result.messages.iter().filter_map(|m| m.uuid.clone()));
+        }
+        for msg in &result.messages {
+            tools.observe(msg);
+        }
 
         let data = serde_json::json!({
             "messages": result.messages,
@@ -1101,15 +1594,161 @@ async fn conversation_stream(
                     if changed_id != session_id {
                         continue;
                     }
-                    let result = storage::get_conversation_stream(&state, &session_id, offset).await;
+                    let mut result = storage::get_conversation_stream(&state, &session_id, offset).await;
                     offset = result.next_offset;
 
+                    if active_only && !result.messages.is_empty() {
+                        let branch = conversation_tree::get_active_branch(&state, &session_id).await;
+                        result.messages.retain(|m| conversation_tree::on_branch(&branch, m));
+                        // Messages already sent left the active branch: the client should refetch
+                        if let Some(ref branch) = branch {
+                            if sent.iter().any(|uuid| !branch.contains(uuid)) {
+                                sent.retain(|uuid| branch.contains(uuid));
+                                let data = serde_json::json!({ "offset": result.next_offset });
+                                yield Ok(Event::default().event("branchChanged").data(data.to_string()));
+                            }
+                        }
+                        sent.extend(result.messages.iter().filter_map(|m| m.uuid.clone()));
+                    }
+
                     if !result.messages.is_empty() {
+                        let mut changed: Vec<usize> = result.messages.iter().flat_map(|m| tools.observe(m)).collect();
+                        changed.sort_unstable();
+                        changed.dedup();
+
                         let data = serde_json::json!({
                             "messages": result.messages,
                             "offset": result.next_offset,
                         });
                         yield Ok(Event::default().event("messages").data(data.to_string()));
+
+                        if !changed.is_empty() {
+                            let calls: Vec<&ToolCall> = changed.iter().map(|&i| tools.get(i)).collect();
+                            let data = serde_json::json!({ "tools": calls });
+                            yield Ok(Event::default().event("tools").data(data.to_string()));
+                        }
+                    }
+                }
+                _ = tokio::time::sleep(Duration::from_secs(30)) => {
+                    let data = serde_json::json!({ "timestamp": chrono_now_ms() });
+                    yield Ok(Event::default().event("heartbeat").data(data.to_string()));
+                }
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Like `conversation_stream`, for one subagent transcript.
+async fn subagent_stream(
+    State(state): State<Arc<AppState>>,
+    Path((session_id, agent_id)): Path<(String, String)>,
+    Query(query): Query<ConversationStreamQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let initial_offset = query.offset.unwrap_or(0);
+
+    let stream = async_stream::stream! {
+        let mut offset = initial_offset;
+        let mut subagent_rx = state.subagent_tx.subscribe();
+        let mut tools = ToolCallTracker::new();
+
+        let result = storage::get_subagent_stream(&state, &session_id, &agent_id, offset).await;
+        offset = result.next_offset;
+        for msg in &result.messages {
+            tools.observe(msg);
+        }
+
+        let data = serde_json::json!({
+            "messages": result.messages,
+            "offset": result.next_offset,
+        });
+        yield Ok(Event::default().event("messages").data(data.to_string()));
+
+        loop {
+            tokio::select! {
+                Ok((changed_session, changed_agent)) = subagent_rx.recv() => {
+                    if changed_session != session_id || changed_agent != agent_id {
+                        continue;
+                    }
+                    let result = storage::get_subagent_stream(&state, &session_id, &agent_id, offset).await;
+                    offset = result.next_offset;
+
+                    if !result.messages.is_empty() {
+                        let mut changed: Vec<usize> = result.messages.iter().flat_map(|m| tools.observe(m)).collect();
+                        changed.sort_unstable();
+                        changed.dedup();
+
+                        let data = serde_json::json!({
+                            "messages": result.messages,
+                            "offset": result.next_offset,
+                        });
+                        yield Ok(Event::default().event("messages").data(data.to_string()));
+
+                        if !changed.is_empty() {
+                            let calls: Vec<&ToolCall> = changed.iter().map(|&i| tools.get(i)).collect();
+                            let data = serde_json::json!({ "tools": calls });
+                            yield Ok(Event::default().event("tools").data(data.to_string()));
+                        }
+                    }
+                }
+                _ = tokio::time::sleep(Duration::from_secs(30)) => {
+                    let data = serde_json::json!({ "timestamp": chrono_now_ms() });
+                    yield Ok(Event::default().event("heartbeat").data(data.to_string()));
+                }
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+async fn get_context_timeline(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match storage::get_context_timeline(&state, &id).await {
+        Some(builder) => Json(builder.timeline()).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
+}
+
+/// Sends the full timeline, then new or updated points as the session grows.
+async fn context_timeline_stream(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let session_id = id;
+
+    let stream = async_stream::stream! {
+        let mut session_rx = state.session_tx.subscribe();
+        let mut builder = storage::get_context_timeline(&state, &session_id)
+            .await
+            .unwrap_or_default();
+
+        let data = serde_json::to_string(builder.timeline()).unwrap_or_default();
+        yield Ok(Event::default().event("timeline").data(data));
+
+        loop {
+            tokio::select! {
+                Ok((changed_id, file_path)) = session_rx.recv() => {
+                    if changed_id != session_id {
+                        continue;
+                    }
+                    let (lines, end_offset) = storage::read_lines_from(&file_path, builder.timeline().end_offset).await;
+                    let update = builder.extend(&lines, end_offset);
+
+                    if !update.points.is_empty() || !update.compactions.is_empty() {
+                        let data = serde_json::json!({
+                            "points": update.points,
+                            "compactions": update.compactions,
+                            "endOffset": end_offset,
+                        });
+                        yield Ok(Event::default().event("points").data(data.to_string()));
                     }
                 }
                 _ = tokio::time::sleep(Duration::from_secs(30)) => {
@@ -1128,6 +1767,7 @@ async fn conversation_stream(
 #[derive(Deserialize)]
 struct ConversationTailQuery {
     limit: Option<usize>,
+    branch: Option<String>,
 }
 
 async fn conversation_tail(
@@ -1136,7 +1776,8 @@ async fn conversation_tail(
     Query(query): Query<ConversationTailQuery>,
 ) -> impl IntoResponse {
     let limit = query.limit.unwrap_or(50);
-    let result = storage::get_conversation_tail(&state, &id, limit).await;
+    let active_only = query.branch.as_deref() == Some("active");
+    let result = storage::get_conversation_tail(&state, &id, limit, active_only).await;
     Json(result)
 }
 
@@ -1144,6 +1785,7 @@ async fn conversation_tail(
 struct ConversationOlderQuery {
     before: u64,
     limit: Option<usize>,
+    branch: Option<String>,
 }
 
 async fn conversation_older(
@@ -1152,10 +1794,51 @@ async fn conversation_older(
     Query(query): Query<ConversationOlderQuery>,
 ) -> impl IntoResponse {
     let limit = query.limit.unwrap_or(50);
-    let result = storage::get_conversation_range(&state, &id, query.before, limit).await;
+    let active_only = query.branch.as_deref() == Some("active");
+    let result = storage::get_conversation_range(&state, &id, query.before, limit, active_only).await;
+    Json(result)
+}
+
+async fn subagent_tail(
+    State(state): State<Arc<AppState>>,
+    Path((id, agent_id)): Path<(String, String)>,
+    Query(query): Query<ConversationTailQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(50);
+    let result = storage::get_subagent_tail(&state, &id, &agent_id, limit).await;
     Json(result)
 }
 
+async fn subagent_older(
+    State(state): State<Arc<AppState>>,
+    Path((id, agent_id)): Path<(String, String)>,
+    Query(query): Query<ConversationOlderQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(50);
+    let result = storage::get_subagent_range(&state, &id, &agent_id, query.before, limit).await;
+    Json(result)
+}
+
+#[derive(Deserialize)]
+struct ConversationAroundQuery {
+    uuid: String,
+    before: Option<usize>,
+    after: Option<usize>,
+}
+
+async fn conversation_around(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<ConversationAroundQuery>,
+) -> impl IntoResponse {
+    let before = query.before.unwrap_or(25);
+    let after = query.after.unwrap_or(25);
+    match storage::get_conversation_around(&state, &id, &query.uuid, before, after).await {
+        Some(result) => Json(result).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "Message not found" }))).into_response(),
+    }
+}
+
 // --- Directory listing ---
 
 async fn get_files(
@@ -1197,8 +1880,8 @@ async fn get_files(
         }
     }
 
-    dirs.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
-    files.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
+    dirs.sort_by_key(|a| a.0.to_lowercase());
+    files.sort_by_key(|a| a.0.to_lowercase());
 
     let mut entries: Vec<serde_json::Value> = dirs.into_iter().map(|(_, v)| v).collect();
     entries.extend(files.into_iter().map(|(_, v)| v));
@@ -1298,9 +1981,9 @@ async fn get_git_diff(
 
         if line.starts_with('+') {
             hunk_adds.push(new_start + hunk_adds.len() as u32);
-        } else if line.starts_with('-') {
+        } else if let Some(old) = line.strip_prefix('-') {
             hunk_dels += 1;
-            hunk_del_lines.push(line[1..].to_string());
+            hunk_del_lines.push(old.to_string());
         }
     }
     flush(hunk_dels, &hunk_del_lines, &hunk_adds, new_start,
@@ -1385,15 +2068,60 @@ fn parse_name_status(text: &str, added: &mut Vec<String>, modified: &mut Vec<Str
         if parts.len() != 2 { continue; }
         let file = parts[1].trim().to_string();
         match parts[0].chars().next() {
-            Some('A') => { if !added.contains(&file) { added.push(file); } }
-            Some('M') => { if !modified.contains(&file) { modified.push(file); } }
-            Some('D') => { if !deleted.contains(&file) { deleted.push(file); } }
-            Some('R') => { if !modified.contains(&file) { modified.push(file); } }
+            Some('A') if !added.contains(&file) => added.push(file),
+            Some('M') | Some('R') if !modified.contains(&file) => modified.push(file),
+            Some('D') if !deleted.contains(&file) => deleted.push(file),
             _ => {}
         }
     }
 }
 
+// --- File → sessions ---
+
+#[derive(Deserialize)]
+struct FileSessionsQuery {
+    path: String,
+}
+
+async fn get_file_sessions(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FileSessionsQuery>,
+) -> impl IntoResponse {
+    let sessions = file_refs::get_file_sessions(&state, &query.path).await;
+    Json(serde_json::json!({ "path": query.path, "sessions": sessions }))
+}
+
+// --- Agent blame ---
+
+async fn get_agent_blame(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FileQuery>,
+) -> Result<Json<AgentBlame>, StatusCode> {
+    let path = PathBuf::from(&query.path);
+    let project = PathBuf::from(&query.project);
+
+    let canon_path = path.canonicalize().map_err(|_| StatusCode::NOT_FOUND)?;
+    let canon_project = project.canonicalize().map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    if !canon_path.starts_with(&canon_project) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let meta = tokio::fs::metadata(&canon_path).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    if meta.len() > 1_048_576 {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    // The paths as given, before symlinks were resolved
+    let rel_path = canon_path.strip_prefix(&canon_project).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let aliases = [query.path.clone(), project.join(rel_path).to_string_lossy().to_string()];
+
+    agent_blame::agent_blame(&state, &canon_project, &canon_path, &aliases)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)
+}
+
 // --- File reader ---
 
 #[derive(Deserialize)]
@@ -1498,7 +2226,7 @@ fn tail_stream(
             let mut watcher = notify::recommended_watcher(move |res: Result<NEvent, notify::Error>| {
                 if let Ok(event) = res {
                     if matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) {
-                        let dominated = file_name.as_ref().map_or(true, |target| {
+                        let dominated = file_name.as_ref().is_none_or(|target| {
                             event.paths.iter().any(|p| p.file_name().map(|n| n == target.as_os_str()).unwrap_or(false))
                         });
                         if dominated {
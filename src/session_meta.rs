@@ -0,0 +1,473 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::compression::session_dir;
+use crate::file_refs::{self, FileRefs};
+use crate::plans;
+use crate::models::{ConversationMessage, PlanRecord, TokenUsage, UsageTotals};
+use crate::state::AppState;
+use crate::storage::{extract_text_from_content, find_session_file, find_subagent_file, head_fingerprint, read_lines_from};
+
+/// Bytes hashed at the start of each file to detect a transcript being replaced.
+const HEAD_FINGERPRINT_LEN: u64 = 256;
+const FIRST_PROMPT_LEN: usize = 100;
+/// gitBranch is read from progress lines near the top of the transcript.
+const BRANCH_SCAN_LINES: usize = 10;
+/// Bump whenever derived fields change — older catalogs are discarded.
+const CATALOG_VERSION: u32 = 6;
+const CATALOG_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Metadata derived from a session transcript, kept up to date by parsing only
+/// the bytes appended since the last refresh.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMeta {
+    pub path: String,
+    /// Byte offset up to which the file has been parsed
+    pub offset: u64,
+    pub head_len: u64,
+    pub head_hash: u64,
+    pub file_size: u64,
+    /// File mtime / birth time in ms since the epoch
+    pub modified_ms: f64,
+    pub created_ms: f64,
+    /// Number of non-empty lines parsed so far
+    pub line_count: usize,
+    /// Number of user/assistant lines
+    pub message_count: usize,
+    /// First non-empty user message, truncated
+    pub first_prompt: Option<String>,
+    pub slug: Option<String>,
+    /// First gitBranch in the first lines of the transcript, other than
+    /// default branches and detached HEAD
+    pub git_branch: Option<String>,
+    /// First working directory recorded in the transcript
+    pub cwd: Option<String>,
+    /// Distinct assistant models, in order of first use
+    pub models: Vec<String>,
+    pub last_timestamp: Option<String>,
+    /// Token usage per UTC day (YYYY-MM-DD), then per model
+    pub usage: BTreeMap<String, BTreeMap<String, UsageTotals>>,
+    pub last_usage: Option<LastUsage>,
+    /// Files referenced by Read/Edit/Write/Bash tool calls. Like `plans`, saved
+    /// in the session's details file rather than the catalog, and shared
+    /// between clones until the next parse
+    #[serde(skip)]
+    pub file_refs: Arc<FileRefs>,
+    /// Plans proposed through ExitPlanMode, in order
+    #[serde(skip)]
+    pub plans: Arc<Vec<PlanRecord>>,
+}
+
+/// Usage counted for the most recent API response. Claude Code writes one line
+/// per content block of a response, each repeating the response's usage, so a
+/// repeat replaces the earlier count instead of adding to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastUsage {
+    pub message_id: String,
+    pub day: String,
+    pub model: String,
+    pub totals: UsageTotals,
+}
+
+impl SessionMeta {
+    fn record_usage(&mut self, val: &serde_json::Value) {
+        let usage = match val
+            .pointer("/message/usage")
+            .and_then(|u| serde_json::from_value::<TokenUsage>(u.clone()).ok())
+        {
+            Some(u) => u,
+            None => return,
+        };
+        let model = match val.pointer("/message/model").and_then(|m| m.as_str()) {
+            Some(m) if m != "<synthetic>" => m.to_string(),
+            _ => return,
+        };
+        let day = match val.get("timestamp").and_then(|t| t.as_str()) {
+            Some(ts) if ts.len() >= 10 => ts[..10].to_string(),
+            _ => return,
+        };
+        let message_id = val
+            .pointer("/message/id")
+            .or_else(|| val.get("requestId"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        if let Some(last) = self.last_usage.take() {
+            if !message_id.is_empty() && last.message_id == message_id {
+                if let Some(totals) = self.usage.get_mut(&last.day).and_then(|m| m.get_mut(&last.model)) {
+                    totals.sub(&last.totals);
+                }
+            }
+        }
+        let totals = UsageTotals::from_usage(&usage);
+        self.usage
+            .entry(day.clone())
+            .or_default()
+            .entry(model.clone())
+            .or_default()
+            .add(&totals);
+        self.last_usage = Some(LastUsage { message_id, day, model, totals });
+    }
+
+    fn apply_line(&mut self, line: &str) {
+        let line_number = self.line_count;
+        self.line_count += 1;
+        let val: serde_json::Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        let str_field = |key: &str| val.get(key).and_then(|v| v.as_str()).filter(|s| !s.is_empty());
+
+        if self.slug.is_none() {
+            self.slug = str_field("slug").map(String::from);
+        }
+        if self.git_branch.is_none() && line_number < BRANCH_SCAN_LINES {
+            self.git_branch = str_field("gitBranch")
+                .filter(|b| *b != "HEAD" && *b != "main" && *b != "master")
+                .map(String::from);
+        }
+        if self.cwd.is_none() {
+            self.cwd = str_field("cwd").map(String::from);
+        }
+        if let Some(ts) = str_field("timestamp") {
+            if self.last_timestamp.as_deref().is_none_or(|last| ts > last) {
+                self.last_timestamp = Some(ts.to_string());
+            }
+        }
+
+        file_refs::record_line(&mut self.file_refs, &val, self.cwd.as_deref());
+        plans::record_line(&mut self.plans, &val);
+
+        let msg_type = val.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        match msg_type {
+            "assistant" => {
+                self.message_count += 1;
+                self.record_usage(&val);
+                let model = val.pointer("/message/model").and_then(|m| m.as_str());
+                if let Some(model) = model {
+                    if !self.models.iter().any(|m| m == model) {
+                        self.models.push(model.to_string());
+                    }
+                }
+            }
+            "user" => {
+                self.message_count += 1;
+                if self.first_prompt.is_none() {
+                    let text = serde_json::from_value::<ConversationMessage>(val)
+                        .ok()
+                        .and_then(|msg| msg.message)
+                        .and_then(|body| body.content)
+                        .map(|content| extract_text_from_content(&content))
+                        .unwrap_or_default();
+                    if !text.is_empty() {
+                        self.first_prompt = Some(text.chars().take(FIRST_PROMPT_LEN).collect());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn epoch_ms(time: std::io::Result<std::time::SystemTime>) -> f64 {
+    time.ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+/// Return up-to-date metadata for a session, parsing only newly appended lines.
+/// Truncated or replaced files are parsed again from the start.
+pub async fn get_session_meta(state: &AppState, session_id: &str) -> Option<SessionMeta> {
+    let file_path = find_session_file(state, session_id).await?;
+    refresh_meta(state, session_id, &file_path).await
+}
+
+/// Metadata of a session's subagent transcripts (`<session>/subagents/*.jsonl`),
+/// cached under `<sessionId>/<agent file stem>`.
+pub async fn get_subagent_metas(state: &AppState, session_id: &str) -> Vec<SessionMeta> {
+    let file_path = match find_session_file(state, session_id).await {
+        Some(p) => p,
+        None => return Vec::new(),
+    };
+    let subagents_dir = format!("{}/subagents", session_dir(&file_path));
+    let mut entries = match fs::read_dir(&subagents_dir).await {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut metas = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let stem = match path.file_stem() {
+            Some(s) => s.to_string_lossy().to_string(),
+            None => continue,
+        };
+        let key = format!("{}/{}", session_id, stem);
+        if let Some(meta) = refresh_meta(state, &key, &path.to_string_lossy()).await {
+            metas.push(meta);
+        }
+    }
+    metas
+}
+
+async fn refresh_meta(state: &AppState, key: &str, file_path: &str) -> Option<SessionMeta> {
+    let stat = match fs::metadata(file_path).await {
+        Ok(m) => m,
+        Err(_) => {
+            forget_meta(state, key);
+            return None;
+        }
+    };
+    let file_size = stat.len();
+
+    let known = state.session_meta.get(key).map(|m| m.value().clone());
+    let old_refs = known.as_ref().map(|m| m.file_refs.clone()).unwrap_or_default();
+    let mut meta = match known {
+        Some(meta)
+            if meta.path == file_path
+                && meta.file_size == file_size
+                && meta.modified_ms == epoch_ms(stat.modified()) =>
+        {
+            return Some(meta);
+        }
+        Some(meta)
+            if meta.path == file_path
+                && file_size >= meta.offset
+                && head_fingerprint(file_path, meta.head_len).await == Some(meta.head_hash) =>
+        {
+            meta
+        }
+        _ => SessionMeta {
+            path: file_path.to_string(),
+            ..Default::default()
+        },
+    };
+
+    let from_offset = meta.offset;
+    let (lines, next_offset) = read_lines_from(file_path, from_offset).await;
+    for (_, line) in &lines {
+        meta.apply_line(line);
+    }
+    meta.offset = next_offset;
+    if from_offset == 0 {
+        meta.head_len = HEAD_FINGERPRINT_LEN.min(next_offset);
+        meta.head_hash = head_fingerprint(file_path, meta.head_len).await.unwrap_or_default();
+    }
+    meta.file_size = file_size;
+    meta.modified_ms = epoch_ms(stat.modified());
+    meta.created_ms = epoch_ms(stat.created());
+
+    if from_offset == 0 || !lines.is_empty() {
+        state.details_dirty.insert(key.to_string(), ());
+    }
+    if !Arc::ptr_eq(&old_refs, &meta.file_refs) {
+        file_refs::update_index(state, key, &old_refs, &meta.file_refs);
+    }
+    state.session_meta.insert(key.to_string(), meta.clone());
+    state.catalog_dirty.store(true, Ordering::Release);
+    Some(meta)
+}
+
+/// Drop a session's (or subagent's) cached metadata, here and on disk.
+pub fn forget_meta(state: &AppState, key: &str) {
+    if let Some((_, meta)) = state.session_meta.remove(key) {
+        file_refs::update_index(state, key, &meta.file_refs, &FileRefs::new());
+        state.details_dirty.insert(key.to_string(), ());
+        state.catalog_dirty.store(true, Ordering::Release);
+    }
+}
+
+// --- Catalog persistence ---
+
+#[derive(Serialize, Deserialize)]
+struct Catalog {
+    version: u32,
+    sessions: HashMap<String, SessionMeta>,
+}
+
+/// The fields of a `SessionMeta` that grow with the transcript, saved to one
+/// file per session so the catalog stays small and only changed sessions are
+/// rewritten. Valid for the catalog entry parsed up to the same offset.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionDetails {
+    offset: u64,
+    head_hash: u64,
+    file_refs: FileRefs,
+    plans: Vec<PlanRecord>,
+}
+
+fn catalog_path(state: &AppState) -> String {
+    format!("{}/session-catalog.json", state.claude_dir)
+}
+
+/// `session-details/<sessionId>.json`, and `session-details/<sessionId>/<agent>.json`
+/// for subagents.
+fn details_path(state: &AppState, key: &str) -> String {
+    format!("{}/session-details/{}.json", state.claude_dir, key)
+}
+
+async fn load_details(state: &AppState, key: &str) -> Option<SessionDetails> {
+    let content = fs::read(details_path(state, key)).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// Write the details files of sessions parsed since the last save, and remove
+/// those of sessions that are gone.
+async fn save_details(state: &AppState) {
+    let keys: Vec<String> = state.details_dirty.iter().map(|e| e.key().clone()).collect();
+    for key in keys {
+        state.details_dirty.remove(&key);
+        let path = details_path(state, &key);
+        let details = state.session_meta.get(&key).map(|meta| SessionDetails {
+            offset: meta.offset,
+            head_hash: meta.head_hash,
+            file_refs: (*meta.file_refs).clone(),
+            plans: (*meta.plans).clone(),
+        });
+        let Some(details) = details else {
+            let _ = fs::remove_file(&path).await;
+            continue;
+        };
+        let Ok(buf) = serde_json::to_vec(&details) else {
+            continue;
+        };
+        if let Some(dir) = std::path::Path::new(&path).parent() {
+            let _ = fs::create_dir_all(dir).await;
+        }
+        let tmp_path = format!("{}.tmp", path);
+        if fs::write(&tmp_path, &buf).await.is_err() || fs::rename(&tmp_path, &path).await.is_err() {
+            eprintln!("[catalog] failed to save {}", path);
+            state.details_dirty.insert(key, ());
+        }
+    }
+}
+
+/// Seed session metadata from the catalog saved by the last run. Entries are
+/// revalidated against the file's size and mtime on first use; those whose
+/// details file is missing or out of step are parsed again.
+pub async fn load_catalog(state: &AppState) {
+    let path = catalog_path(state);
+    let content = match fs::read(&path).await {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    let catalog = match serde_json::from_slice::<Catalog>(&content) {
+        Ok(c) if c.version == CATALOG_VERSION => c,
+        _ => {
+            eprintln!("[catalog] catalog is corrupt or outdated, rebuilding");
+            let _ = fs::remove_file(&path).await;
+            return;
+        }
+    };
+    let total = catalog.sessions.len();
+    let mut loaded = 0;
+    for (key, mut meta) in catalog.sessions {
+        match load_details(state, &key).await {
+            Some(details) if details.offset == meta.offset && details.head_hash == meta.head_hash => {
+                file_refs::update_index(state, &key, &FileRefs::new(), &details.file_refs);
+                meta.file_refs = Arc::new(details.file_refs);
+                meta.plans = Arc::new(details.plans);
+                state.session_meta.insert(key, meta);
+                loaded += 1;
+            }
+            _ => {}
+        }
+    }
+    eprintln!("[catalog] loaded {} of {} sessions", loaded, total);
+}
+
+/// Persist the catalog if it changed since the last save (write to a temp file, then rename).
+pub async fn save_catalog(state: &AppState) {
+    // Details first: a catalog entry is only used along with its details file
+    save_details(state).await;
+    if !state.catalog_dirty.swap(false, Ordering::AcqRel) {
+        return;
+    }
+    let catalog = Catalog {
+        version: CATALOG_VERSION,
+        sessions: state
+            .session_meta
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect(),
+    };
+    let buf = match serde_json::to_vec(&catalog) {
+        Ok(b) => b,
+        Err(_) => return,
+    };
+    let path = catalog_path(state);
+    let tmp_path = format!("{}.tmp", path);
+    if fs::write(&tmp_path, &buf).await.is_err() || fs::rename(&tmp_path, &path).await.is_err() {
+        eprintln!("[catalog] failed to save catalog");
+        state.catalog_dirty.store(true, Ordering::Release);
+    }
+}
+
+/// Reconcile the loaded catalog with the files on disk (only changed files are
+/// re-read), then save it periodically.
+pub fn spawn_catalog_sync(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        // Files that vanished while we were not running. Subagent entries are
+        // kept while their parent session exists and revalidated on use.
+        let stale: Vec<String> = state
+            .session_meta
+            .iter()
+            .filter(|e| match e.key().split_once('/') {
+                Some((parent, _)) => !state.file_index.contains_key(parent),
+                None => state.file_index.get(e.key()).is_none_or(|p| *p.value() != e.value().path),
+            })
+            .map(|e| e.key().clone())
+            .collect();
+        for session_id in stale {
+            forget_meta(&state, &session_id);
+        }
+
+        let ids: Vec<String> = state
+            .file_index
+            .iter()
+            .filter(|e| !e.key().starts_with("agent-"))
+            .map(|e| e.key().clone())
+            .collect();
+        for session_id in &ids {
+            get_session_meta(&state, session_id).await;
+            get_subagent_metas(&state, session_id).await;
+        }
+        save_catalog(&state).await;
+
+        // Keep subagent metadata (and the file index) current as their transcripts grow
+        let watcher_state = state.clone();
+        let mut subagent_rx = state.subagent_tx.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match subagent_rx.recv().await {
+                    Ok((session_id, agent_id)) => {
+                        if let Some(path) = find_subagent_file(&watcher_state, &session_id, &agent_id).await {
+                            let key = format!("{}/agent-{}", session_id, agent_id);
+                            refresh_meta(&watcher_state, &key, &path).await;
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                }
+            }
+        });
+
+        loop {
+            tokio::time::sleep(CATALOG_SAVE_INTERVAL).await;
+            save_catalog(&state).await;
+        }
+    });
+}
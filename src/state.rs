@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Instant;
@@ -5,11 +6,43 @@ use std::time::Instant;
 use dashmap::DashMap;
 use tokio::sync::{broadcast, Mutex, RwLock};
 
-use crate::models::{HistoryEntry, PushSubscription, SessionStatus, UsageResponse};
+use crate::federation::{Peer, PeerSession};
+use crate::models::{HistoryEntry, PushSubscription, SessionStatus, SessionUserMeta, UsageResponse};
+use crate::search_index::SearchIndex;
+use crate::session_meta::SessionMeta;
+
+/// A Claude home directory (`~/.claude` or another `CLAUDE_CONFIG_DIR`) whose
+/// sessions are shown, with the label it is shown under.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub label: String,
+    pub claude_dir: String,
+    pub projects_dir: String,
+}
+
+impl Source {
+    pub fn new(label: String, claude_dir: String) -> Self {
+        let projects_dir = format!("{}/projects", claude_dir);
+        Self { label, claude_dir, projects_dir }
+    }
+
+    /// Whether this is `~/.claude`, which claude uses without `CLAUDE_CONFIG_DIR`.
+    pub fn is_default_home(&self) -> bool {
+        dirs::home_dir().is_some_and(|h| std::path::Path::new(&self.claude_dir) == h.join(".claude"))
+    }
+}
 
 pub struct AppState {
+    // The first source: claude-run keeps its own files (catalog, search index,
+    // trash, user metadata, push keys) here
     pub claude_dir: String,
     pub projects_dir: String,
+    // Every Claude home directory watched, the first one included
+    pub sources: Vec<Source>,
+    // Other claude-run instances whose sessions are merged in
+    pub peers: Vec<Peer>,
+    // Bearer token required from non-loopback API clients (`--token`)
+    pub api_token: Option<String>,
     pub dev_mode: bool,
 
     // sessionId → file path
@@ -22,23 +55,31 @@ pub struct AppState {
     pub permission_messages: DashMap<String, String>,
     // sessionId → AskUserQuestion data (questions array from tool_input)
     pub question_data: DashMap<String, serde_json::Value>,
-    // sessionId → slug (plan name, cached from JSONL)
-    pub slug_cache: DashMap<String, Option<String>>,
-    // sessionId → git branch (cached from JSONL)
-    pub git_branch_cache: DashMap<String, Option<String>>,
+    // sessionId → metadata derived from the JSONL (count, slug, branch, cwd, ...)
+    pub session_meta: DashMap<String, SessionMeta>,
+    // session_meta changed since the catalog was last saved
+    pub catalog_dirty: AtomicBool,
+    // session_meta keys whose details file (file refs, plans) needs rewriting
+    pub details_dirty: DashMap<String, ()>,
+    // absolute path → session_meta keys whose tool calls referenced it
+    pub file_ref_index: DashMap<String, HashSet<String>>,
     // (project, branch) → (PR URL, PR number) (cached from gh CLI)
     pub pr_cache: DashMap<(String, String), Option<(String, u64)>>,
-    // sessionId → (message_count, file_size) — invalidated when file grows
-    pub message_count_cache: DashMap<String, (usize, u64)>,
     // sessionId → (summary, message_count_at_generation)
     pub summary_cache: DashMap<String, (String, usize)>,
     // sessionId → true while generating (avoid duplicate spawns)
     pub summary_pending: DashMap<String, bool>,
     // Sessions hidden from UI (deleted by user)
     pub hidden_sessions: DashMap<String, ()>,
+    // sessionId → stars, tags, title and notes set by the user
+    pub user_meta: DashMap<String, SessionUserMeta>,
+    // sessionId → session owned by a peer
+    pub peer_sessions: DashMap<String, PeerSession>,
     // Parsed history.jsonl cache
     pub history_cache: RwLock<Option<Vec<HistoryEntry>>>,
     pub history_dirty: AtomicBool,
+    // Persistent full-text search index
+    pub search_index: SearchIndex,
     // Usage API cache (response, fetched_at)
     pub usage_cache: Mutex<Option<(Instant, UsageResponse)>>,
 
@@ -54,34 +95,50 @@ pub struct AppState {
     // Broadcast channels for SSE
     pub history_tx: broadcast::Sender<()>,
     pub session_tx: broadcast::Sender<(String, String)>, // (sessionId, filePath)
+    pub subagent_tx: broadcast::Sender<(String, String)>, // (sessionId, agentId)
     pub status_tx: broadcast::Sender<(String, SessionStatus)>,
     pub url_tx: broadcast::Sender<String>,
 }
 
 impl AppState {
-    pub fn new(claude_dir: String, dev_mode: bool, vapid_private_pem: Vec<u8>, vapid_public_base64: String) -> Arc<Self> {
-        let projects_dir = format!("{}/projects", claude_dir);
+    pub fn new(
+        sources: Vec<Source>,
+        peers: Vec<Peer>,
+        api_token: Option<String>,
+        dev_mode: bool,
+        vapid_private_pem: Vec<u8>,
+        vapid_public_base64: String,
+    ) -> Arc<Self> {
+        let claude_dir = sources[0].claude_dir.clone();
+        let projects_dir = sources[0].projects_dir.clone();
         let (history_tx, _) = broadcast::channel(64);
         let (session_tx, _) = broadcast::channel(256);
+        let (subagent_tx, _) = broadcast::channel(256);
         let (status_tx, _) = broadcast::channel(64);
         let (url_tx, _) = broadcast::channel(16);
 
         Arc::new(Self {
             claude_dir,
             projects_dir,
+            sources,
+            peers,
+            api_token,
             dev_mode,
             file_index: DashMap::new(),
             session_statuses: DashMap::new(),
             session_panes: DashMap::new(),
             permission_messages: DashMap::new(),
             question_data: DashMap::new(),
-            slug_cache: DashMap::new(),
-            git_branch_cache: DashMap::new(),
+            session_meta: DashMap::new(),
+            catalog_dirty: AtomicBool::new(false),
+            details_dirty: DashMap::new(),
+            file_ref_index: DashMap::new(),
             pr_cache: DashMap::new(),
-            message_count_cache: DashMap::new(),
             summary_cache: DashMap::new(),
             summary_pending: DashMap::new(),
             hidden_sessions: DashMap::new(),
+            user_meta: DashMap::new(),
+            peer_sessions: DashMap::new(),
             last_mobile_ping: AtomicU64::new(0),
             last_desktop_ping: AtomicU64::new(0),
             push_subscriptions: DashMap::new(),
@@ -89,14 +146,38 @@ impl AppState {
             vapid_public_base64,
             history_cache: RwLock::new(None),
             history_dirty: AtomicBool::new(false),
+            search_index: SearchIndex::new(),
             usage_cache: Mutex::new(None),
             history_tx,
             session_tx,
+            subagent_tx,
             status_tx,
             url_tx,
         })
     }
 
+    /// The source a transcript path belongs to (the first source if none matches).
+    pub fn source_of_path(&self, path: &str) -> &Source {
+        self.sources
+            .iter()
+            .filter(|s| path.starts_with(&format!("{}/", s.projects_dir)))
+            .max_by_key(|s| s.projects_dir.len())
+            .unwrap_or(&self.sources[0])
+    }
+
+    /// The source a session's transcript lives in.
+    pub fn session_source(&self, session_id: &str) -> &Source {
+        match self.file_index.get(session_id) {
+            Some(path) => self.source_of_path(path.value()),
+            None => &self.sources[0],
+        }
+    }
+
+    /// Label shown for a session, only when more than one source is configured.
+    pub fn source_label(&self, session_id: &str) -> Option<String> {
+        (self.sources.len() > 1).then(|| self.session_source(session_id).label.clone())
+    }
+
     pub fn invalidate_history_cache(&self) {
         self.history_dirty.store(true, Ordering::Release);
     }
@@ -1,12 +1,19 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use tokio::fs;
-use tokio::io::{AsyncBufReadExt, AsyncSeekExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader};
 
+use crate::compression::{read_transcript, session_dir, session_id_from_file_name, TranscriptFile};
+use crate::conversation_tree::{active_branch, on_branch};
 use crate::models::*;
+use crate::search_index::IndexHit;
+use crate::search_query::{Filter, SearchQuery};
+use crate::session_meta::get_session_meta;
 use crate::state::AppState;
+use crate::trash;
+use crate::user_meta;
 
 /// Extract task-id from a string containing <task-notification> XML.
 fn extract_task_id(text: &str) -> Option<String> {
@@ -49,118 +56,25 @@ pub fn decode_project_path(encoded: &str) -> String {
     }
 }
 
-async fn get_session_slug(state: &AppState, session_id: &str) -> Option<String> {
-    if let Some(cached) = state.slug_cache.get(session_id) {
-        return cached.value().clone();
-    }
-
-    let file_path = match find_session_file(state, session_id).await {
-        Some(p) => p,
-        None => {
-            state.slug_cache.insert(session_id.to_string(), None);
-            return None;
-        }
-    };
-
-    let file = match tokio::fs::File::open(&file_path).await {
-        Ok(f) => f,
-        Err(_) => {
-            state.slug_cache.insert(session_id.to_string(), None);
-            return None;
-        }
-    };
-
-    let reader = tokio::io::BufReader::new(file);
-    let mut lines = reader.lines();
-    while let Ok(Some(line)) = lines.next_line().await {
-        if let Ok(val) = serde_json::from_str::<serde_json::Value>(&line) {
-            if let Some(slug) = val.get("slug").and_then(|s| s.as_str()) {
-                let slug = slug.to_string();
-                state.slug_cache.insert(session_id.to_string(), Some(slug.clone()));
-                return Some(slug);
-            }
-        }
-    }
-
-    state.slug_cache.insert(session_id.to_string(), None);
-    None
+/// Project path implied by a session file's directory name. Lossy: `-` may have
+/// been `/`, `.` or a literal `-`, so prefer the transcript's `cwd`.
+pub fn project_path_from_file(file_path: &str) -> String {
+    let dir_name = std::path::Path::new(file_path)
+        .parent()
+        .and_then(|p| p.file_name())
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    decode_project_path(&dir_name)
 }
 
-async fn get_session_git_branch(state: &AppState, session_id: &str) -> Option<String> {
-    if let Some(cached) = state.git_branch_cache.get(session_id) {
-        return cached.value().clone();
+/// Real project path for a session: the `cwd` recorded in its transcript, falling
+/// back to the decoded project directory name.
+pub async fn get_session_project(state: &AppState, session_id: &str) -> Option<String> {
+    if let Some(cwd) = get_session_meta(state, session_id).await.and_then(|m| m.cwd) {
+        return Some(cwd);
     }
-
-    let file_path = match find_session_file(state, session_id).await {
-        Some(p) => p,
-        None => {
-            state.git_branch_cache.insert(session_id.to_string(), None);
-            return None;
-        }
-    };
-
-    // Read first ~10 lines looking for gitBranch (appears on progress lines near the top)
-    let content = match tokio::fs::read_to_string(&file_path).await {
-        Ok(c) => c,
-        Err(_) => {
-            state.git_branch_cache.insert(session_id.to_string(), None);
-            return None;
-        }
-    };
-
-    for (i, line) in content.lines().enumerate() {
-        if i >= 10 {
-            break;
-        }
-        if !line.contains("gitBranch") {
-            continue;
-        }
-        if let Ok(val) = serde_json::from_str::<serde_json::Value>(line) {
-            if let Some(branch) = val.get("gitBranch").and_then(|s| s.as_str()) {
-                if !branch.is_empty() && branch != "HEAD" && branch != "main" && branch != "master" {
-                    let branch = branch.to_string();
-                    state.git_branch_cache.insert(session_id.to_string(), Some(branch.clone()));
-                    return Some(branch);
-                }
-            }
-        }
-    }
-
-    state.git_branch_cache.insert(session_id.to_string(), None);
-    None
-}
-
-async fn get_first_user_message(state: &AppState, session_id: &str) -> String {
-    let file_path = match find_session_file(state, session_id).await {
-        Some(p) => p,
-        None => return session_id.to_string(),
-    };
-
-    let content = match fs::read_to_string(&file_path).await {
-        Ok(c) => c,
-        Err(_) => return session_id.to_string(),
-    };
-
-    for line in content.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        if let Ok(msg) = serde_json::from_str::<ConversationMessage>(line) {
-            if msg.msg_type == "user" {
-                if let Some(ref message) = msg.message {
-                    if let Some(ref content) = message.content {
-                        let text = extract_text_from_content(content);
-                        if !text.is_empty() {
-                            let truncated: String = text.chars().take(100).collect();
-                            return truncated;
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    session_id.to_string()
+    let file_path = state.file_index.get(session_id)?.value().clone();
+    Some(project_path_from_file(&file_path))
 }
 
 pub fn get_project_name(project_path: &str) -> String {
@@ -172,7 +86,12 @@ pub fn get_project_name(project_path: &str) -> String {
 }
 
 pub async fn build_file_index(state: &AppState) {
-    let projects_dir = &state.projects_dir;
+    for source in &state.sources {
+        index_projects_dir(state, &source.projects_dir).await;
+    }
+}
+
+async fn index_projects_dir(state: &AppState, projects_dir: &str) {
     let mut entries = match fs::read_dir(projects_dir).await {
         Ok(e) => e,
         Err(_) => return,
@@ -188,37 +107,33 @@ pub async fn build_file_index(state: &AppState) {
             Err(_) => continue,
         };
         while let Ok(Some(file_entry)) = sub_entries.next_entry().await {
-            let file_path = file_entry.path();
-            if let Some(ext) = file_path.extension() {
-                if ext == "jsonl" {
-                    if let Some(stem) = file_path.file_stem() {
-                        let session_id = stem.to_string_lossy().to_string();
-                        state
-                            .file_index
-                            .insert(session_id, file_path.to_string_lossy().to_string());
-                    }
-                }
+            let file_name = file_entry.file_name().to_string_lossy().to_string();
+            if let Some(session_id) = session_id_from_file_name(&file_name) {
+                state
+                    .file_index
+                    .insert(session_id.to_string(), file_entry.path().to_string_lossy().to_string());
             }
         }
     }
 }
 
+/// History entries of every source, each source's in file order.
 pub async fn load_history_cache(state: &AppState) -> Vec<HistoryEntry> {
-    let history_path = format!("{}/history.jsonl", state.claude_dir);
-    let content = match fs::read_to_string(&history_path).await {
-        Ok(c) => c,
-        Err(_) => {
-            let mut cache = state.history_cache.write().await;
-            *cache = Some(Vec::new());
-            return Vec::new();
-        }
-    };
-
-    let entries: Vec<HistoryEntry> = content
-        .lines()
-        .filter(|l| !l.trim().is_empty())
-        .filter_map(|line| serde_json::from_str(line).ok())
-        .collect();
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    for (index, source) in state.sources.iter().enumerate() {
+        let history_path = format!("{}/history.jsonl", source.claude_dir);
+        let content = match fs::read_to_string(&history_path).await {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        entries.extend(
+            content
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .filter_map(|line| serde_json::from_str::<HistoryEntry>(line).ok())
+                .map(|entry| HistoryEntry { source: index, ..entry }),
+        );
+    }
 
     let mut cache = state.history_cache.write().await;
     *cache = Some(entries.clone());
@@ -289,9 +204,11 @@ async fn find_session_by_timestamp(
 
     while let Ok(Some(entry)) = entries.next_entry().await {
         let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
-            continue;
-        }
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let session_id = match session_id_from_file_name(&file_name) {
+            Some(id) => id.to_string(),
+            None => continue,
+        };
         if let Ok(meta) = fs::metadata(&path).await {
             if let Ok(modified) = meta.modified() {
                 let mtime_ms = modified
@@ -302,9 +219,7 @@ async fn find_session_by_timestamp(
                 let diff = (mtime_ms - timestamp).abs();
                 if diff < closest_diff {
                     closest_diff = diff;
-                    closest_file = path
-                        .file_stem()
-                        .map(|s| s.to_string_lossy().to_string());
+                    closest_file = Some(session_id);
                 }
             }
         }
@@ -319,26 +234,29 @@ pub async fn find_session_file(state: &AppState, session_id: &str) -> Option<Str
         return Some(entry.value().clone());
     }
 
-    // Fallback: scan all project directories
-    let target_file = format!("{}.jsonl", session_id);
-    let mut entries = fs::read_dir(&state.projects_dir).await.ok()?;
-
-    while let Ok(Some(dir_entry)) = entries.next_entry().await {
-        let dir_path = dir_entry.path();
-        if !dir_path.is_dir() {
-            continue;
-        }
-        let mut sub_entries = match fs::read_dir(&dir_path).await {
+    // Fallback: scan every source's project directories (plain or compressed transcripts)
+    for source in &state.sources {
+        let mut entries = match fs::read_dir(&source.projects_dir).await {
             Ok(e) => e,
             Err(_) => continue,
         };
-        while let Ok(Some(file_entry)) = sub_entries.next_entry().await {
-            if file_entry.file_name().to_string_lossy() == target_file {
-                let file_path = file_entry.path().to_string_lossy().to_string();
-                state
-                    .file_index
-                    .insert(session_id.to_string(), file_path.clone());
-                return Some(file_path);
+        while let Ok(Some(dir_entry)) = entries.next_entry().await {
+            let dir_path = dir_entry.path();
+            if !dir_path.is_dir() {
+                continue;
+            }
+            let mut sub_entries = match fs::read_dir(&dir_path).await {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+            while let Ok(Some(file_entry)) = sub_entries.next_entry().await {
+                if session_id_from_file_name(&file_entry.file_name().to_string_lossy()) == Some(session_id) {
+                    let file_path = file_entry.path().to_string_lossy().to_string();
+                    state
+                        .file_index
+                        .insert(session_id.to_string(), file_path.clone());
+                    return Some(file_path);
+                }
             }
         }
     }
@@ -347,47 +265,88 @@ pub async fn find_session_file(state: &AppState, session_id: &str) -> Option<Str
 }
 
 pub async fn count_session_messages(state: &AppState, session_id: &str) -> usize {
-    let file_path = match find_session_file(state, session_id).await {
-        Some(p) => p,
-        None => return 0,
-    };
+    get_session_meta(state, session_id)
+        .await
+        .map(|m| m.message_count)
+        .unwrap_or(0)
+}
+
+/// FNV-1a hash of the first `len` bytes of a file, used to detect a transcript
+/// being replaced rather than appended to.
+pub async fn head_fingerprint(file_path: &str, len: u64) -> Option<u64> {
+    let mut file = TranscriptFile::open(file_path).await.ok()?;
+    let mut buf = vec![0u8; len as usize];
+    file.read_exact(&mut buf).await.ok()?;
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in buf {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    Some(hash)
+}
 
-    let file_size = match fs::metadata(&file_path).await {
-        Ok(m) => m.len(),
-        Err(_) => return 0,
+/// Read complete lines starting at `from_offset`.
+/// Returns each line with its starting byte offset, and the offset just past the
+/// last complete line (a partially written trailing line is left for next time).
+pub async fn read_lines_from(file_path: &str, from_offset: u64) -> (Vec<(u64, String)>, u64) {
+    let mut file = match TranscriptFile::open(file_path).await {
+        Ok(f) => f,
+        Err(_) => return (Vec::new(), from_offset),
     };
+    if from_offset > 0 && file.seek(std::io::SeekFrom::Start(from_offset)).await.is_err() {
+        return (Vec::new(), from_offset);
+    }
 
-    // Return cached count if file size hasn't changed
-    if let Some(cached) = state.message_count_cache.get(session_id) {
-        if cached.1 == file_size {
-            return cached.0;
+    let mut reader = BufReader::new(file);
+    let mut lines = Vec::new();
+    let mut offset = from_offset;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                if buf.last() != Some(&b'\n') {
+                    break;
+                }
+                let line = String::from_utf8_lossy(&buf[..n - 1]);
+                if !line.trim().is_empty() {
+                    lines.push((offset, line.into_owned()));
+                }
+                offset += n as u64;
+            }
         }
     }
 
-    let content = match fs::read_to_string(&file_path).await {
-        Ok(c) => c,
-        Err(_) => return 0,
-    };
+    (lines, offset)
+}
 
-    let count = content
-        .lines()
-        .filter(|l| !l.trim().is_empty())
-        .filter_map(|line| {
-            let v: serde_json::Value = serde_json::from_str(line).ok()?;
-            let t = v.get("type")?.as_str()?;
-            if t == "user" || t == "assistant" {
-                Some(())
-            } else {
-                None
-            }
-        })
-        .count();
+/// Read the single lines starting at each of `offsets` (sorted ascending).
+pub async fn read_lines_at(file_path: &str, offsets: &[u64]) -> Vec<Option<String>> {
+    let file = match TranscriptFile::open(file_path).await {
+        Ok(f) => f,
+        Err(_) => return vec![None; offsets.len()],
+    };
 
-    state.message_count_cache.insert(session_id.to_string(), (count, file_size));
-    count
+    let mut reader = BufReader::new(file);
+    let mut lines = Vec::with_capacity(offsets.len());
+    for &offset in offsets {
+        if reader.seek(std::io::SeekFrom::Start(offset)).await.is_err() {
+            lines.push(None);
+            continue;
+        }
+        let mut buf = Vec::new();
+        lines.push(match reader.read_until(b'\n', &mut buf).await {
+            Ok(n) if n > 0 => Some(String::from_utf8_lossy(&buf).trim_end().to_string()),
+            _ => None,
+        });
+    }
+    lines
 }
 
-pub async fn get_sessions(state: &AppState) -> Vec<Session> {
+/// All visible sessions, pinned first and then by last activity. With `tag`,
+/// only sessions carrying that user tag.
+pub async fn get_sessions(state: &AppState, tag: Option<&str>) -> Vec<Session> {
     // Check dirty flag — always reload if history.jsonl changed
     let is_dirty = state.history_dirty.swap(false, std::sync::atomic::Ordering::AcqRel);
     let entries = if is_dirty {
@@ -416,7 +375,8 @@ pub async fn get_sessions(state: &AppState) -> Vec<Session> {
             sid.clone()
         } else {
             let encoded = encode_project_path(&entry.project);
-            match find_session_by_timestamp(&state.projects_dir, &encoded, entry.timestamp).await {
+            let projects_dir = &state.sources[entry.source].projects_dir;
+            match find_session_by_timestamp(projects_dir, &encoded, entry.timestamp).await {
                 Some(id) => id,
                 None => continue,
             }
@@ -427,44 +387,33 @@ pub async fn get_sessions(state: &AppState) -> Vec<Session> {
         }
         seen_ids.insert(session_id.clone());
 
-        let file_path = find_session_file(state, &session_id).await;
-        let message_count = match &file_path {
-            Some(_) => count_session_messages(state, &session_id).await,
-            None => 0,
-        };
-
-        let mut last_activity = entry.timestamp;
-        let mut file_size = None;
-        if let Some(ref fp) = file_path {
-            if let Ok(meta) = fs::metadata(fp).await {
-                file_size = Some(meta.len());
-                if let Ok(modified) = meta.modified() {
-                    last_activity = modified
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_secs_f64()
-                        * 1000.0;
-                }
-            }
-        }
-
-        let slug = get_session_slug(state, &session_id).await;
-        let git_branch = get_session_git_branch(state, &session_id).await;
+        let meta = get_session_meta(state, &session_id).await;
+        let message_count = meta.as_ref().map(|m| m.message_count).unwrap_or(0);
+        let last_activity = meta.as_ref().map(|m| m.modified_ms).unwrap_or(entry.timestamp);
+        let file_size = meta.as_ref().map(|m| m.file_size);
+        let slug = meta.as_ref().and_then(|m| m.slug.clone());
+        let git_branch = meta.as_ref().and_then(|m| m.git_branch.clone());
         let summary = state.summary_cache.get(&session_id).map(|v| v.0.clone());
+        let user = user_meta::get_user_meta(state, &session_id);
 
         let display = if entry.display.contains("** Session started from claude-run **") {
             "New session".to_string()
         } else {
             entry.display.clone()
         };
+        let project = if entry.project.is_empty() {
+            meta.as_ref().and_then(|m| m.cwd.clone()).unwrap_or_default()
+        } else {
+            entry.project.clone()
+        };
 
         sessions.push(Session {
             id: session_id.clone(),
             display,
             timestamp: entry.timestamp,
             last_activity,
-            project: entry.project.clone(),
-            project_name: get_project_name(&entry.project),
+            project_name: get_project_name(&project),
+            project,
             message_count,
             status: state.get_session_status(&session_id),
             pane_id: state.get_session_pane(&session_id).map(|(id, _, _)| id),
@@ -476,6 +425,13 @@ pub async fn get_sessions(state: &AppState) -> Vec<Session> {
             git_branch,
             summary,
             file_size,
+            starred: user.starred,
+            pinned: user.pinned,
+            tags: user.tags,
+            title: user.title,
+            notes: user.notes,
+            source: state.source_label(&session_id),
+            host: None,
         });
     }
 
@@ -488,50 +444,25 @@ pub async fn get_sessions(state: &AppState) -> Vec<Session> {
         seen_ids.insert(session_id.clone());
 
         let file_path = entry.value().clone();
-        let message_count = count_session_messages(state, &session_id).await;
-        if message_count == 0 {
-            continue;
-        }
-
-        // Extract project from file path: .../projects/<encoded_project>/<sessionId>.jsonl
-        let project_path = std::path::Path::new(&file_path);
-        let project_dir = project_path.parent().unwrap_or(std::path::Path::new(""));
-        let encoded_project = project_dir
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_default();
+        let meta = match get_session_meta(state, &session_id).await {
+            Some(m) if m.message_count > 0 => m,
+            _ => continue,
+        };
 
-        // Decode project path: replace leading - with /, then remaining - that were / or .
-        let project = decode_project_path(&encoded_project);
+        // Prefer the recorded cwd over the lossy decoded directory name
+        let project = meta.cwd.clone().unwrap_or_else(|| project_path_from_file(&file_path));
         let project_name = get_project_name(&project);
 
-        // Get first user message as display text
-        let display = get_first_user_message(state, &session_id).await;
-
-        let mut last_activity = 0.0_f64;
-        let mut timestamp = 0.0_f64;
-        let mut file_size = None;
-        if let Ok(meta) = fs::metadata(&file_path).await {
-            file_size = Some(meta.len());
-            if let Ok(modified) = meta.modified() {
-                last_activity = modified
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs_f64()
-                    * 1000.0;
-            }
-            if let Ok(created) = meta.created() {
-                timestamp = created
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs_f64()
-                    * 1000.0;
-            }
-        }
-
-        let slug = get_session_slug(state, &session_id).await;
-        let git_branch = get_session_git_branch(state, &session_id).await;
+        // First user message as display text
+        let display = meta.first_prompt.clone().unwrap_or_else(|| session_id.clone());
+        let message_count = meta.message_count;
+        let last_activity = meta.modified_ms;
+        let timestamp = meta.created_ms;
+        let file_size = Some(meta.file_size);
+        let slug = meta.slug.clone();
+        let git_branch = meta.git_branch.clone();
         let summary = state.summary_cache.get(&session_id).map(|v| v.0.clone());
+        let user = user_meta::get_user_meta(state, &session_id);
 
         sessions.push(Session {
             id: session_id.clone(),
@@ -551,13 +482,32 @@ pub async fn get_sessions(state: &AppState) -> Vec<Session> {
             git_branch,
             summary,
             file_size,
+            starred: user.starred,
+            pinned: user.pinned,
+            tags: user.tags,
+            title: user.title,
+            notes: user.notes,
+            source: state.source_label(&session_id),
+            host: None,
         });
     }
 
-    sessions.sort_by(|a, b| b.last_activity.partial_cmp(&a.last_activity).unwrap_or(std::cmp::Ordering::Equal));
+    if let Some(tag) = tag {
+        sessions.retain(|s| s.tags.iter().any(|t| t == tag));
+    }
+    sort_sessions(&mut sessions);
     sessions
 }
 
+/// Pinned sessions first, then most recently active first.
+pub fn sort_sessions(sessions: &mut [Session]) {
+    sessions.sort_by(|a, b| {
+        b.pinned
+            .cmp(&a.pinned)
+            .then_with(|| b.last_activity.partial_cmp(&a.last_activity).unwrap_or(std::cmp::Ordering::Equal))
+    });
+}
+
 pub async fn get_projects(state: &AppState) -> Vec<String> {
     let entries = {
         let cache = state.history_cache.read().await;
@@ -570,29 +520,34 @@ pub async fn get_projects(state: &AppState) -> Vec<String> {
         }
     };
 
-    let mut projects: Vec<String> = entries
+    let mut projects: HashSet<String> = entries
         .iter()
         .filter(|e| !e.project.is_empty())
         .map(|e| e.project.clone())
-        .collect::<HashSet<_>>()
-        .into_iter()
         .collect();
+    // Sessions missing from history.jsonl still carry their real cwd
+    projects.extend(state.session_meta.iter().filter_map(|m| m.cwd.clone()));
 
+    let mut projects: Vec<String> = projects.into_iter().collect();
     projects.sort();
     projects
 }
 
-pub async fn get_conversation(state: &AppState, session_id: &str) -> Vec<ConversationMessage> {
+/// All displayed messages in file order. With `active_only`, messages on
+/// abandoned branches (rewinds, edited prompts, sidechains) are left out.
+pub async fn get_conversation(state: &AppState, session_id: &str, active_only: bool) -> Vec<ConversationMessage> {
     let file_path = match find_session_file(state, session_id).await {
         Some(p) => p,
         None => return Vec::new(),
     };
 
-    let content = match fs::read_to_string(&file_path).await {
-        Ok(c) => c,
+    let content = match read_transcript(&file_path).await {
+        Ok(c) => String::from_utf8_lossy(&c).into_owned(),
         Err(_) => return Vec::new(),
     };
 
+    let branch = if active_only { active_branch(content.as_bytes()) } else { None };
+
     let mut messages = Vec::new();
     let mut summaries = Vec::new();
     let mut queue_ops = Vec::new();
@@ -606,7 +561,7 @@ pub async fn get_conversation(state: &AppState, session_id: &str) -> Vec<Convers
         }
         if let Ok(msg) = serde_json::from_str::<ConversationMessage>(line) {
             match msg.msg_type.as_str() {
-                "user" | "assistant" => {
+                "user" | "assistant" if on_branch(&branch, &msg) => {
                     // Track task-ids from user messages containing task-notifications
                     if msg.msg_type == "user" {
                         if let Some(ref message) = msg.message {
@@ -662,17 +617,33 @@ pub async fn get_conversation_stream(
     session_id: &str,
     from_offset: u64,
 ) -> StreamResult {
-    let file_path = match find_session_file(state, session_id).await {
-        Some(p) => p,
-        None => {
-            return StreamResult {
-                messages: Vec::new(),
-                next_offset: 0,
-            }
-        }
-    };
+    match find_session_file(state, session_id).await {
+        Some(file_path) => read_stream(&file_path, from_offset).await,
+        None => StreamResult {
+            messages: Vec::new(),
+            next_offset: 0,
+        },
+    }
+}
 
-    let file = match tokio::fs::File::open(&file_path).await {
+pub async fn get_subagent_stream(
+    state: &AppState,
+    session_id: &str,
+    agent_id: &str,
+    from_offset: u64,
+) -> StreamResult {
+    match find_subagent_file(state, session_id, agent_id).await {
+        Some(file_path) => read_stream(&file_path, from_offset).await,
+        None => StreamResult {
+            messages: Vec::new(),
+            next_offset: 0,
+        },
+    }
+}
+
+/// Messages appended to a transcript since `from_offset`.
+async fn read_stream(file_path: &str, from_offset: u64) -> StreamResult {
+    let file = match TranscriptFile::open(file_path).await {
         Ok(f) => f,
         Err(_) => {
             return StreamResult {
@@ -682,8 +653,8 @@ pub async fn get_conversation_stream(
         }
     };
 
-    let file_size = match file.metadata().await {
-        Ok(m) => m.len(),
+    let file_size = match file.len().await {
+        Ok(len) => len,
         Err(_) => {
             return StreamResult {
                 messages: Vec::new(),
@@ -763,21 +734,25 @@ pub async fn get_conversation_tail(
     state: &AppState,
     session_id: &str,
     limit: usize,
+    active_only: bool,
 ) -> PaginatedResult {
-    let file_path = match find_session_file(state, session_id).await {
-        Some(p) => p,
-        None => {
-            return PaginatedResult {
-                messages: Vec::new(),
-                start_offset: 0,
-                end_offset: 0,
-                has_more: false,
-            }
-        }
-    };
+    match find_session_file(state, session_id).await {
+        Some(file_path) => read_tail(&file_path, limit, active_only).await,
+        None => PaginatedResult::default(),
+    }
+}
+
+/// The last N messages of a subagent transcript.
+pub async fn get_subagent_tail(state: &AppState, session_id: &str, agent_id: &str, limit: usize) -> PaginatedResult {
+    match find_subagent_file(state, session_id, agent_id).await {
+        Some(file_path) => read_tail(&file_path, limit, false).await,
+        None => PaginatedResult::default(),
+    }
+}
 
+async fn read_tail(file_path: &str, limit: usize, active_only: bool) -> PaginatedResult {
     // Read entire file to parse all messages with their byte offsets
-    let content = match fs::read(&file_path).await {
+    let content = match read_transcript(file_path).await {
         Ok(c) => c,
         Err(_) => {
             return PaginatedResult {
@@ -785,11 +760,13 @@ pub async fn get_conversation_tail(
                 start_offset: 0,
                 end_offset: 0,
                 has_more: false,
+                has_newer: false,
             }
         }
     };
 
     let file_size = content.len() as u64;
+    let branch = if active_only { active_branch(&content) } else { None };
 
     // Parse all lines, tracking byte offsets
     let mut entries: Vec<(u64, u64, ConversationMessage)> = Vec::new(); // (start, end, msg)
@@ -803,7 +780,9 @@ pub async fn get_conversation_tail(
             if let Ok(line_str) = std::str::from_utf8(line) {
                 if let Ok(msg) = serde_json::from_str::<ConversationMessage>(line_str) {
                     if msg.msg_type == "user" || msg.msg_type == "assistant" || msg.msg_type == "summary" {
-                        entries.push((offset, line_end, msg));
+                        if on_branch(&branch, &msg) {
+                            entries.push((offset, line_end, msg));
+                        }
                     } else if msg.msg_type == "queue-operation" {
                         if let Some(m) = queue_op_to_user_message(&msg) {
                             entries.push((offset, line_end, m));
@@ -822,6 +801,7 @@ pub async fn get_conversation_tail(
             start_offset: 0,
             end_offset: file_size,
             has_more: false,
+            has_newer: false,
         };
     }
 
@@ -847,6 +827,7 @@ pub async fn get_conversation_tail(
         start_offset,
         end_offset: file_size,
         has_more,
+        has_newer: false,
     }
 }
 
@@ -856,30 +837,35 @@ pub async fn get_conversation_range(
     session_id: &str,
     end_offset: u64,
     limit: usize,
+    active_only: bool,
 ) -> PaginatedResult {
-    let file_path = match find_session_file(state, session_id).await {
-        Some(p) => p,
-        None => {
-            return PaginatedResult {
-                messages: Vec::new(),
-                start_offset: 0,
-                end_offset: 0,
-                has_more: false,
-            }
-        }
-    };
+    match find_session_file(state, session_id).await {
+        Some(file_path) => read_range(&file_path, end_offset, limit, active_only).await,
+        None => PaginatedResult::default(),
+    }
+}
 
+/// Subagent messages before `end_offset` (for loading older messages).
+pub async fn get_subagent_range(
+    state: &AppState,
+    session_id: &str,
+    agent_id: &str,
+    end_offset: u64,
+    limit: usize,
+) -> PaginatedResult {
+    match find_subagent_file(state, session_id, agent_id).await {
+        Some(file_path) => read_range(&file_path, end_offset, limit, false).await,
+        None => PaginatedResult::default(),
+    }
+}
+
+async fn read_range(file_path: &str, end_offset: u64, limit: usize, active_only: bool) -> PaginatedResult {
     if end_offset == 0 {
-        return PaginatedResult {
-            messages: Vec::new(),
-            start_offset: 0,
-            end_offset: 0,
-            has_more: false,
-        };
+        return PaginatedResult::default();
     }
 
     // Read up to end_offset
-    let content = match fs::read(&file_path).await {
+    let content = match read_transcript(file_path).await {
         Ok(c) => c,
         Err(_) => {
             return PaginatedResult {
@@ -887,10 +873,13 @@ pub async fn get_conversation_range(
                 start_offset: 0,
                 end_offset,
                 has_more: false,
+                has_newer: false,
             }
         }
     };
 
+    // The active leaf is at the end of the file, so resolve the branch before cutting
+    let branch = if active_only { active_branch(&content) } else { None };
     let read_end = (end_offset as usize).min(content.len());
     let content = &content[..read_end];
 
@@ -910,7 +899,9 @@ pub async fn get_conversation_range(
             if let Ok(line_str) = std::str::from_utf8(line) {
                 if let Ok(msg) = serde_json::from_str::<ConversationMessage>(line_str) {
                     if msg.msg_type == "user" || msg.msg_type == "assistant" || msg.msg_type == "summary" {
-                        entries.push((offset, line_end, msg));
+                        if on_branch(&branch, &msg) {
+                            entries.push((offset, line_end, msg));
+                        }
                     } else if msg.msg_type == "queue-operation" {
                         if let Some(m) = queue_op_to_user_message(&msg) {
                             entries.push((offset, line_end, m));
@@ -929,6 +920,7 @@ pub async fn get_conversation_range(
             start_offset: 0,
             end_offset,
             has_more: false,
+            has_newer: false,
         };
     }
 
@@ -954,11 +946,322 @@ pub async fn get_conversation_range(
         start_offset,
         end_offset,
         has_more,
+        has_newer: false,
+    }
+}
+
+/// Parse a line as a message shown in the conversation view, or `None` for
+/// bookkeeping lines (progress, snapshots, ...).
+fn parse_display_line(line: &str) -> Option<ConversationMessage> {
+    let msg: ConversationMessage = serde_json::from_str(line).ok()?;
+    match msg.msg_type.as_str() {
+        "user" | "assistant" | "summary" => Some(msg),
+        "queue-operation" => queue_op_to_user_message(&msg),
+        _ => None,
+    }
+}
+
+const BACKWARD_CHUNK: u64 = 64 * 1024;
+
+/// Read displayable messages that end before `end_offset` (a line start), walking
+/// the file backwards in chunks until `limit` are found.
+/// Returns `(start, msg)` entries oldest-first and whether older messages remain.
+async fn read_messages_before(
+    file: &mut TranscriptFile,
+    end_offset: u64,
+    limit: usize,
+) -> (Vec<(u64, ConversationMessage)>, bool) {
+    use tokio::io::AsyncReadExt;
+
+    let mut found = Vec::new();
+    // Bytes [pending_start, end) not yet split into lines
+    let mut pending: Vec<u8> = Vec::new();
+    let mut pending_start = end_offset;
+
+    loop {
+        // Split complete lines off the end of the pending buffer
+        loop {
+            let body_end = if pending.last() == Some(&b'\n') { pending.len() - 1 } else { pending.len() };
+            let line_start = match pending[..body_end].iter().rposition(|&b| b == b'\n') {
+                Some(i) => i + 1,
+                None if pending_start == 0 && !pending.is_empty() => 0,
+                None => break,
+            };
+            if let Ok(line) = std::str::from_utf8(&pending[line_start..body_end]) {
+                if let Some(msg) = parse_display_line(line) {
+                    if found.len() == limit {
+                        found.reverse();
+                        return (found, true);
+                    }
+                    found.push((pending_start + line_start as u64, msg));
+                }
+            }
+            pending.truncate(line_start);
+        }
+
+        if pending_start == 0 {
+            break;
+        }
+        let chunk_start = pending_start.saturating_sub(BACKWARD_CHUNK);
+        let mut chunk = vec![0u8; (pending_start - chunk_start) as usize];
+        if file.seek(std::io::SeekFrom::Start(chunk_start)).await.is_err()
+            || file.read_exact(&mut chunk).await.is_err()
+        {
+            break;
+        }
+        chunk.extend_from_slice(&pending);
+        pending = chunk;
+        pending_start = chunk_start;
     }
+
+    found.reverse();
+    (found, false)
+}
+
+/// Read up to `limit` displayable messages starting at `from_offset`.
+/// Returns `(start, msg)` entries, the offset after the last line read, and whether
+/// more messages follow.
+async fn read_messages_after(
+    file: &mut TranscriptFile,
+    from_offset: u64,
+    limit: usize,
+) -> (Vec<(u64, ConversationMessage)>, u64, bool) {
+    let mut found = Vec::new();
+    if file.seek(std::io::SeekFrom::Start(from_offset)).await.is_err() {
+        return (found, from_offset, false);
+    }
+
+    let mut reader = BufReader::new(file);
+    let mut offset = from_offset;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let n = match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        if buf.last() != Some(&b'\n') {
+            // Partially written line — leave it for the SSE stream
+            break;
+        }
+        if let Ok(line) = std::str::from_utf8(&buf[..n - 1]) {
+            if let Some(msg) = parse_display_line(line) {
+                if found.len() == limit {
+                    return (found, offset, true);
+                }
+                found.push((offset, msg));
+            }
+        }
+        offset += n as u64;
+    }
+
+    (found, offset, false)
+}
+
+/// Find the byte offset of the line holding message `uuid`, using the search
+/// index when possible and scanning the file otherwise.
+async fn locate_message(state: &AppState, session_id: &str, file_path: &str, uuid: &str) -> Option<u64> {
+    if let Some(offset) = state.search_index.locate(session_id, uuid) {
+        return Some(offset);
+    }
+
+    let needle = format!("\"{}\"", uuid);
+    let (lines, _) = read_lines_from(file_path, 0).await;
+    lines
+        .into_iter()
+        .filter(|(_, line)| line.contains(&needle))
+        .find(|(_, line)| {
+            serde_json::from_str::<ConversationMessage>(line)
+                .is_ok_and(|m| m.uuid.as_deref() == Some(uuid))
+        })
+        .map(|(offset, _)| offset)
+}
+
+/// Get a window of messages centred on message `uuid`: up to `before` older
+/// messages, the message itself, and up to `after` newer ones.
+/// Returns `None` if the session or message can't be found.
+pub async fn get_conversation_around(
+    state: &AppState,
+    session_id: &str,
+    uuid: &str,
+    before: usize,
+    after: usize,
+) -> Option<PaginatedResult> {
+    let file_path = find_session_file(state, session_id).await?;
+    let offset = locate_message(state, session_id, &file_path, uuid).await?;
+
+    let mut file = TranscriptFile::open(&file_path).await.ok()?;
+    let (older, has_more) = read_messages_before(&mut file, offset, before).await;
+    let (newer, end_offset, has_newer) = read_messages_after(&mut file, offset, after + 1).await;
+    if newer.first().map(|(o, _)| *o) != Some(offset) {
+        return None;
+    }
+
+    let start_offset = if has_more {
+        older.first().map(|(o, _)| *o).unwrap_or(offset)
+    } else {
+        0
+    };
+
+    Some(PaginatedResult {
+        messages: older.into_iter().chain(newer).map(|(_, msg)| msg).collect(),
+        start_offset,
+        end_offset,
+        has_more,
+        has_newer,
+    })
+}
+
+/// Builds the context timeline line by line, so the SSE stream can keep
+/// feeding it appended lines.
+#[derive(Default)]
+pub struct ContextTimelineBuilder {
+    timeline: ContextTimeline,
+    /// API response id of the last point: Claude Code writes one line per
+    /// content block, each repeating the response's usage
+    last_message_id: Option<String>,
+    /// Compaction waiting for the next point to learn its turn
+    pending_compaction: Option<usize>,
+}
+
+/// Points and compactions touched by one batch of lines.
+pub struct ContextTimelineUpdate {
+    pub points: Vec<ContextPoint>,
+    pub compactions: Vec<CompactionMarker>,
+}
+
+impl ContextTimelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn observe(&mut self, offset: u64, line: &str, changed_points: &mut Vec<usize>, changed_compactions: &mut Vec<usize>) {
+        let val: serde_json::Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        let str_field = |key: &str| val.get(key).and_then(|v| v.as_str()).map(String::from);
+        let msg_type = val.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let subtype = val.get("subtype").and_then(|t| t.as_str()).unwrap_or("");
+        let compactions = &mut self.timeline.compactions;
+
+        // Plain `summary` lines are session titles, not compactions
+        if msg_type == "system" && subtype == "compact_boundary" {
+            compactions.push(CompactionMarker {
+                turn: self.timeline.points.len(),
+                offset,
+                timestamp: str_field("timestamp"),
+                trigger: val.pointer("/compactMetadata/trigger").and_then(|v| v.as_str()).map(String::from),
+                pre_tokens: val.pointer("/compactMetadata/preTokens").and_then(|v| v.as_u64()),
+                summary: str_field("summary"),
+            });
+            self.pending_compaction = Some(compactions.len() - 1);
+            changed_compactions.push(compactions.len() - 1);
+            return;
+        }
+
+        // The summary of a compact_boundary follows as a user message; older
+        // transcripts have the summary message without a boundary record
+        if msg_type == "user" && val.get("isCompactSummary").and_then(|v| v.as_bool()) == Some(true) {
+            let text = serde_json::from_value::<ConversationMessage>(val.clone())
+                .map(|m| extract_message_text(&m))
+                .unwrap_or_default();
+            let i = match self.pending_compaction {
+                Some(i) if compactions[i].summary.is_none() => {
+                    compactions[i].summary = Some(text);
+                    i
+                }
+                Some(_) => return,
+                None => {
+                    compactions.push(CompactionMarker {
+                        turn: self.timeline.points.len(),
+                        offset,
+                        timestamp: str_field("timestamp"),
+                        trigger: None,
+                        pre_tokens: None,
+                        summary: Some(text),
+                    });
+                    self.pending_compaction = Some(compactions.len() - 1);
+                    compactions.len() - 1
+                }
+            };
+            changed_compactions.push(i);
+            return;
+        }
+
+        if msg_type != "assistant" {
+            return;
+        }
+        let usage = match val
+            .pointer("/message/usage")
+            .and_then(|u| serde_json::from_value::<TokenUsage>(u.clone()).ok())
+        {
+            Some(u) => u,
+            None => return,
+        };
+        let cache_read = usage.cache_read_input_tokens.unwrap_or(0);
+        let cache_creation = usage.cache_creation_input_tokens.unwrap_or(0);
+        let context_tokens = usage.input_tokens.unwrap_or(0) + cache_creation + cache_read;
+        let message_id = val.pointer("/message/id").and_then(|v| v.as_str()).map(String::from);
+
+        let points = &mut self.timeline.points;
+        let repeat = message_id.is_some() && message_id == self.last_message_id && !points.is_empty();
+        let turn = if repeat { points.len() - 1 } else { points.len() };
+        let point = ContextPoint {
+            turn,
+            uuid: str_field("uuid"),
+            timestamp: str_field("timestamp"),
+            context_tokens,
+            output_tokens: usage.output_tokens.unwrap_or(0),
+            cache_read_tokens: cache_read,
+            cache_creation_tokens: cache_creation,
+            cache_read_ratio: if context_tokens > 0 { cache_read as f64 / context_tokens as f64 } else { 0.0 },
+        };
+        if repeat {
+            points[turn] = point;
+        } else {
+            points.push(point);
+        }
+        if !changed_points.contains(&turn) {
+            changed_points.push(turn);
+        }
+        self.timeline.peak_context_tokens = self.timeline.peak_context_tokens.max(context_tokens);
+        self.last_message_id = message_id;
+        self.pending_compaction = None;
+    }
+
+    /// Feed lines appended to the transcript and return what changed.
+    pub fn extend(&mut self, lines: &[(u64, String)], end_offset: u64) -> ContextTimelineUpdate {
+        let mut changed_points = Vec::new();
+        let mut changed_compactions = Vec::new();
+        for (offset, line) in lines {
+            self.observe(*offset, line, &mut changed_points, &mut changed_compactions);
+        }
+        self.timeline.end_offset = end_offset;
+        changed_compactions.sort_unstable();
+        changed_compactions.dedup();
+        ContextTimelineUpdate {
+            points: changed_points.iter().map(|&i| self.timeline.points[i].clone()).collect(),
+            compactions: changed_compactions.iter().map(|&i| self.timeline.compactions[i].clone()).collect(),
+        }
+    }
+
+    pub fn timeline(&self) -> &ContextTimeline {
+        &self.timeline
+    }
+}
+
+/// Per-turn context size, cache efficiency and compactions of a session.
+pub async fn get_context_timeline(state: &AppState, session_id: &str) -> Option<ContextTimelineBuilder> {
+    let file_path = find_session_file(state, session_id).await?;
+    let (lines, end_offset) = read_lines_from(&file_path, 0).await;
+    let mut builder = ContextTimelineBuilder::new();
+    builder.extend(&lines, end_offset);
+    Some(builder)
 }
 
 pub async fn delete_session(state: &AppState, session_id: &str) -> bool {
-    let history_path = format!("{}/history.jsonl", state.claude_dir);
+    let history_path = format!("{}/history.jsonl", state.session_source(session_id).claude_dir);
 
     let content = match fs::read_to_string(&history_path).await {
         Ok(c) => c,
@@ -967,10 +1270,12 @@ pub async fn delete_session(state: &AppState, session_id: &str) -> bool {
 
     let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
     let mut filtered = Vec::new();
+    let mut removed = Vec::new();
 
     for line in &lines {
         if let Ok(entry) = serde_json::from_str::<HistoryEntry>(line) {
             if entry.session_id.as_deref() == Some(session_id) {
+                removed.push(line.to_string());
                 continue;
             }
         }
@@ -985,7 +1290,7 @@ pub async fn delete_session(state: &AppState, session_id: &str) -> bool {
 
     if filtered.len() != lines.len() {
         let new_content = filtered.join("\n") + "\n";
-        if fs::write(&history_path, new_content).await.is_err() {
+        if write_atomic(&history_path, new_content.as_bytes()).await.is_err() {
             return false;
         }
     }
@@ -1007,18 +1312,28 @@ pub async fn delete_session(state: &AppState, session_id: &str) -> bool {
         use tokio::io::AsyncWriteExt;
         let _ = f.write_all(format!("{}\n", session_id).as_bytes()).await;
     }
+    trash::save_record(state, session_id, removed).await;
+    let _ = state.history_tx.send(());
 
     true
 }
 
+/// Replace a file's content atomically (write a temp file, then rename), so a
+/// crash never leaves it half-written.
+pub async fn write_atomic(path: &str, content: &[u8]) -> std::io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, content).await?;
+    fs::rename(&tmp_path, path).await
+}
+
 pub async fn get_subagent_map(state: &AppState, session_id: &str) -> Vec<SubagentInfo> {
     let file_path = match find_session_file(state, session_id).await {
         Some(p) => p,
         None => return Vec::new(),
     };
 
-    let content = match fs::read_to_string(&file_path).await {
-        Ok(c) => c,
+    let content = match read_transcript(&file_path).await {
+        Ok(c) => String::from_utf8_lossy(&c).into_owned(),
         Err(_) => return Vec::new(),
     };
 
@@ -1052,21 +1367,29 @@ pub async fn get_subagent_map(state: &AppState, session_id: &str) -> Vec<Subagen
     infos
 }
 
+/// Path of a subagent transcript, if it exists.
+pub async fn find_subagent_file(state: &AppState, session_id: &str, agent_id: &str) -> Option<String> {
+    if agent_id.is_empty() || agent_id.contains(['/', '\\']) || agent_id.contains("..") {
+        return None;
+    }
+    let file_path = find_session_file(state, session_id).await?;
+
+    // Session file: <projects>/<encoded>/<sessionId>.jsonl
+    // Subagent: <projects>/<encoded>/<sessionId>/subagents/agent-<agentId>.jsonl
+    let subagent_path = format!("{}/subagents/agent-{}.jsonl", session_dir(&file_path), agent_id);
+    fs::try_exists(&subagent_path).await.unwrap_or(false).then_some(subagent_path)
+}
+
 pub async fn get_subagent_conversation(
     state: &AppState,
     session_id: &str,
     agent_id: &str,
 ) -> Vec<ConversationMessage> {
-    let file_path = match find_session_file(state, session_id).await {
+    let subagent_path = match find_subagent_file(state, session_id, agent_id).await {
         Some(p) => p,
         None => return Vec::new(),
     };
 
-    // Session file: <projects>/<encoded>/<sessionId>.jsonl
-    // Subagent: <projects>/<encoded>/<sessionId>/subagents/agent-<agentId>.jsonl
-    let session_dir = file_path.trim_end_matches(".jsonl");
-    let subagent_path = format!("{}/subagents/agent-{}.jsonl", session_dir, agent_id);
-
     let content = match fs::read_to_string(&subagent_path).await {
         Ok(c) => c,
         Err(_) => return Vec::new(),
@@ -1088,7 +1411,7 @@ pub async fn get_subagent_conversation(
 
 pub async fn get_plan_session_map(state: &AppState, session_id: &str) -> Vec<PlanSessionInfo> {
     // 1. Get the slug for this session
-    let slug = match get_session_slug(state, session_id).await {
+    let slug = match get_session_meta(state, session_id).await.and_then(|m| m.slug) {
         Some(s) => s,
         None => return Vec::new(),
     };
@@ -1098,8 +1421,8 @@ pub async fn get_plan_session_map(state: &AppState, session_id: &str) -> Vec<Pla
         Some(p) => p,
         None => return Vec::new(),
     };
-    let content = match fs::read_to_string(&file_path).await {
-        Ok(c) => c,
+    let content = match read_transcript(&file_path).await {
+        Ok(c) => String::from_utf8_lossy(&c).into_owned(),
         Err(_) => return Vec::new(),
     };
 
@@ -1136,7 +1459,7 @@ pub async fn get_plan_session_map(state: &AppState, session_id: &str) -> Vec<Pla
         if other_id == session_id {
             continue;
         }
-        if let Some(other_slug) = get_session_slug(state, &other_id).await {
+        if let Some(other_slug) = get_session_meta(state, &other_id).await.and_then(|m| m.slug) {
             if other_slug == slug {
                 plan_sessions.push(other_id);
             }
@@ -1162,7 +1485,7 @@ pub async fn get_plan_session_map(state: &AppState, session_id: &str) -> Vec<Pla
     result
 }
 
-fn extract_text_from_content(content: &MessageContent) -> String {
+pub fn extract_text_from_content(content: &MessageContent) -> String {
     match content {
         MessageContent::Text(s) => s.clone(),
         MessageContent::Blocks(blocks) => {
@@ -1195,7 +1518,7 @@ fn extract_text_from_content(content: &MessageContent) -> String {
     }
 }
 
-fn extract_message_text(msg: &ConversationMessage) -> String {
+pub fn extract_message_text(msg: &ConversationMessage) -> String {
     if let Some(ref summary) = msg.summary {
         return summary.clone();
     }
@@ -1233,21 +1556,100 @@ fn create_snippet(text: &str, query: &str, context_length: usize) -> String {
     snippet
 }
 
+fn message_blocks(msg: &ConversationMessage) -> &[ContentBlock] {
+    match msg.message.as_ref().and_then(|m| m.content.as_ref()) {
+        Some(MessageContent::Blocks(blocks)) => blocks,
+        _ => &[],
+    }
+}
+
+/// Session-level filters (`project:`), checked before any file is read.
+fn session_matches(session: &Session, query: &SearchQuery) -> bool {
+    query.terms.iter().all(|term| match &term.filter {
+        Filter::Project(project) => session.project.to_lowercase().contains(project.as_str()) != term.negated,
+        _ => true,
+    })
+}
+
+/// Message-level filters, evaluated against the message and its content blocks.
+fn message_matches(msg: &ConversationMessage, text: &str, lower_text: &str, query: &SearchQuery) -> bool {
+    query.terms.iter().all(|term| {
+        let hit = match &term.filter {
+            Filter::Text(s) => lower_text.contains(s.as_str()),
+            Filter::Regex(re) => re.is_match(text),
+            Filter::Project(_) => return true,
+            Filter::Branch(branch) => {
+                msg.extra.get("gitBranch").and_then(|v| v.as_str()) == Some(branch.as_str())
+            }
+            Filter::Role(role) => msg.msg_type == *role,
+            Filter::Tool(name) => message_blocks(msg).iter().any(|b| {
+                b.block_type == "tool_use" && b.name.as_ref().is_some_and(|n| n.to_lowercase() == *name)
+            }),
+            Filter::Model(model) => msg
+                .message
+                .as_ref()
+                .and_then(|m| m.model.as_ref())
+                .is_some_and(|m| m.to_lowercase().contains(model.as_str())),
+            Filter::Before(date) => msg
+                .timestamp
+                .as_ref()
+                .and_then(|ts| ts.get(..10))
+                .is_some_and(|day| day < date.as_str()),
+            Filter::After(date) => msg
+                .timestamp
+                .as_ref()
+                .and_then(|ts| ts.get(..10))
+                .is_some_and(|day| day > date.as_str()),
+            Filter::IsError => message_blocks(msg)
+                .iter()
+                .any(|b| b.block_type == "tool_result" && b.is_error == Some(true)),
+        };
+        hit != term.negated
+    })
+}
+
+fn match_message(
+    msg: &ConversationMessage,
+    query: &SearchQuery,
+    message_index: usize,
+    offset: u64,
+) -> Option<SearchMatch> {
+    let text = extract_message_text(msg);
+    if !message_matches(msg, &text, &text.to_lowercase(), query) {
+        return None;
+    }
+    let snippet = match query.highlight() {
+        Some(Filter::Text(s)) => create_snippet(&text, s, 60),
+        Some(Filter::Regex(re)) => create_snippet(&text, re.find(&text).map(|m| m.as_str()).unwrap_or(""), 60),
+        _ => create_snippet(&text, "", 60),
+    };
+    let truncated: String = text.chars().take(200).collect();
+    Some(SearchMatch {
+        message_index,
+        uuid: msg.uuid.clone(),
+        offset,
+        text: truncated,
+        snippet,
+    })
+}
+
 async fn search_session_file(
     file_path: &str,
     _session_id: &str,
-    query: &str,
+    query: &SearchQuery,
 ) -> Vec<SearchMatch> {
-    let content = match fs::read_to_string(file_path).await {
-        Ok(c) => c,
+    let content = match read_transcript(file_path).await {
+        Ok(c) => String::from_utf8_lossy(&c).into_owned(),
         Err(_) => return Vec::new(),
     };
 
-    let lower_query = query.to_lowercase();
     let mut matches = Vec::new();
     let mut message_index = 0;
+    let mut next_offset: u64 = 0;
 
-    for line in content.lines() {
+    for line in content.split('\n') {
+        let offset = next_offset;
+        next_offset += line.len() as u64 + 1;
         if line.trim().is_empty() {
             continue;
         }
@@ -1260,14 +1662,8 @@ async fn search_session_file(
             continue;
         }
 
-        let text = extract_message_text(&msg);
-        if text.to_lowercase().contains(&lower_query) {
-            let truncated: String = text.chars().take(200).collect();
-            matches.push(SearchMatch {
-                message_index,
-                text: truncated,
-                snippet: create_snippet(&text, query, 60),
-            });
+        if let Some(m) = match_message(&msg, query, message_index, offset) {
+            matches.push(m);
         }
         message_index += 1;
     }
@@ -1275,52 +1671,225 @@ async fn search_session_file(
     matches
 }
 
-pub async fn search_conversations(state: &Arc<AppState>, query: &str) -> Vec<SearchResult> {
-    let query = query.trim();
-    if query.is_empty() {
-        return Vec::new();
-    }
+/// Confirm index candidates by re-reading only their lines.
+async fn search_index_hits(file_path: &str, hits: &[IndexHit], query: &SearchQuery) -> Vec<SearchMatch> {
+    let offsets: Vec<u64> = hits.iter().map(|h| h.offset).collect();
+    let lines = read_lines_at(file_path, &offsets).await;
 
-    let sessions = get_sessions(state).await;
+    hits.iter()
+        .zip(lines)
+        .filter_map(|(hit, line)| {
+            let msg: ConversationMessage = serde_json::from_str(&line?).ok()?;
+            match_message(&msg, query, hit.message_index, hit.offset)
+        })
+        .collect()
+}
 
-    let mut handles = Vec::new();
-    for session in &sessions {
-        let session_id = session.id.clone();
-        let display = session.display.clone();
-        let project_name = session.project_name.clone();
-        let timestamp = session.timestamp;
-        let query = query.to_string();
-        let state = state.clone();
+/// Maximum number of session files searched at the same time.
+const SEARCH_CONCURRENCY: usize = 8;
 
-        handles.push(tokio::spawn(async move {
-            let file_path = match find_session_file(&state, &session_id).await {
-                Some(p) => p,
-                None => return None,
-            };
+struct SearchJob {
+    session_id: String,
+    display: String,
+    project_name: String,
+    timestamp: f64,
+    hits: Option<Vec<IndexHit>>,
+}
+
+/// An in-progress search over all sessions with bounded concurrency.
+/// Dropping it aborts the session searches still running.
+pub struct SearchRun {
+    state: Arc<AppState>,
+    query: SearchQuery,
+    jobs: std::vec::IntoIter<SearchJob>,
+    running: tokio::task::JoinSet<Option<SearchResult>>,
+    totals: SearchTotals,
+}
+
+impl SearchRun {
+    pub async fn start(state: &Arc<AppState>, query: &SearchQuery) -> Self {
+        let mut jobs = Vec::new();
+        let mut sessions_searched = 0;
+
+        if !query.is_empty() {
+            // Candidates from the persistent index when the query has text terms of
+            // three or more characters; otherwise files are scanned
+            let mut index_hits: Option<HashMap<String, Vec<IndexHit>>> = state
+                .search_index
+                .candidates(&query.index_terms())
+                .map(|hits| {
+                    let mut by_session: HashMap<String, Vec<IndexHit>> = HashMap::new();
+                    for hit in hits {
+                        by_session.entry(hit.session_id.clone()).or_default().push(hit);
+                    }
+                    by_session
+                });
+
+            for session in get_sessions(state, None).await {
+                if !session_matches(&session, query) {
+                    continue;
+                }
+                sessions_searched += 1;
+                let hits = index_hits
+                    .as_mut()
+                    .map(|by_session| by_session.remove(&session.id).unwrap_or_default());
+                jobs.push(SearchJob {
+                    session_id: session.id,
+                    display: session.display,
+                    project_name: session.project_name,
+                    timestamp: session.timestamp,
+                    hits,
+                });
+            }
+        }
+
+        Self {
+            state: state.clone(),
+            query: query.clone(),
+            totals: SearchTotals {
+                sessions_searched,
+                sessions_matched: 0,
+                matches: 0,
+            },
+            jobs: jobs.into_iter(),
+            running: tokio::task::JoinSet::new(),
+        }
+    }
 
-            let matches = search_session_file(&file_path, &session_id, &query).await;
+    fn spawn_next(&mut self) -> bool {
+        let Some(job) = self.jobs.next() else {
+            return false;
+        };
+        let state = self.state.clone();
+        let query = self.query.clone();
+        self.running.spawn(async move {
+            let file_path = find_session_file(&state, &job.session_id).await?;
+
+            // Sessions not indexed yet, or with lines appended since, are scanned
+            let matches = match job.hits {
+                Some(hits) if state.search_index.is_current(&job.session_id, &file_path).await => {
+                    search_index_hits(&file_path, &hits, &query).await
+                }
+                _ => search_session_file(&file_path, &job.session_id, &query).await,
+            };
             if matches.is_empty() {
                 return None;
             }
 
             Some(SearchResult {
-                session_id,
-                display,
-                project_name,
-                timestamp,
+                source: state.source_label(&job.session_id),
+                host: None,
+                session_id: job.session_id,
+                display: job.display,
+                project_name: job.project_name,
+                timestamp: job.timestamp,
                 matches,
             })
-        }));
+        });
+        true
     }
 
-    let mut results = Vec::new();
-    for handle in handles {
-        if let Ok(Some(result)) = handle.await {
-            results.push(result);
+    /// Next session with matches, in completion order. `None` once every session is done.
+    pub async fn next(&mut self) -> Option<SearchResult> {
+        loop {
+            while self.running.len() < SEARCH_CONCURRENCY && self.spawn_next() {}
+            match self.running.join_next().await? {
+                Ok(Some(result)) => {
+                    self.totals.sessions_matched += 1;
+                    self.totals.matches += result.matches.len();
+                    return Some(result);
+                }
+                _ => continue,
+            }
         }
     }
 
+    pub fn totals(&self) -> &SearchTotals {
+        &self.totals
+    }
+}
+
+pub async fn search_conversations(state: &Arc<AppState>, query: &SearchQuery) -> Vec<SearchResult> {
+    let mut run = SearchRun::start(state, query).await;
+    let mut results = Vec::new();
+    while let Some(result) = run.next().await {
+        results.push(result);
+    }
+
     results.sort_by(|a, b| b.timestamp.partial_cmp(&a.timestamp).unwrap_or(std::cmp::Ordering::Equal));
     results
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_index::{update_file, SearchIndex};
+
+    #[tokio::test]
+    async fn indexed_search_matches_scan_for_mid_word_queries() {
+        let dir = std::env::temp_dir().join(format!("claude-run-search-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session.jsonl").to_string_lossy().into_owned();
+        let texts = [
+            "foo bar",
+            "load myconfig.toml",
+            "read the session_id field",
+            "Ünïcode CONFIG values",
+            "nothing to see",
+        ];
+        let lines: Vec<String> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                serde_json::json!({
+                    "type": if i % 2 == 0 { "user" } else { "assistant" },
+                    "uuid": format!("u{}", i),
+                    "message": { "role": "user", "content": text },
+                })
+                .to_string()
+            })
+            .collect();
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        let index = SearchIndex::new();
+        update_file(&index, "s", &path).await;
+        assert!(index.is_current("s", &path).await);
+
+        for input in ["oo", "foo", "config", "_id", "sion_i", "ünï", "\"the session\"", "fig val", "zzz"] {
+            let query = crate::search_query::parse(input).unwrap();
+            let scanned = search_session_file(&path, "s", &query).await;
+            let indexed = match index.candidates(&query.index_terms()) {
+                Some(hits) => search_index_hits(&path, &hits, &query).await,
+                None => search_session_file(&path, "s", &query).await,
+            };
+            let key = |m: &Vec<SearchMatch>| m.iter().map(|m| (m.message_index, m.offset)).collect::<Vec<_>>();
+            assert_eq!(key(&scanned), key(&indexed), "query {:?}", input);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn context_timeline_ignores_title_summaries() {
+        let lines: Vec<(u64, String)> = [
+            r#"{"type":"summary","summary":"Fix the login page","leafUuid":"a"}"#,
+            r#"{"type":"assistant","uuid":"a","message":{"id":"m1","usage":{"input_tokens":10,"output_tokens":5}}}"#,
+            r#"{"type":"system","subtype":"compact_boundary","compactMetadata":{"trigger":"auto","preTokens":900}}"#,
+            r#"{"type":"user","isCompactSummary":true,"message":{"role":"user","content":"Earlier work"}}"#,
+            r#"{"type":"assistant","uuid":"b","message":{"id":"m2","usage":{"input_tokens":20,"output_tokens":5}}}"#,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, l)| (i as u64 * 100, l.to_string()))
+        .collect();
+        let mut builder = ContextTimelineBuilder::new();
+        builder.extend(&lines, 500);
+
+        let compactions = &builder.timeline().compactions;
+        assert_eq!(compactions.len(), 1);
+        assert_eq!(compactions[0].turn, 1);
+        assert_eq!(compactions[0].offset, 200);
+        assert_eq!(compactions[0].trigger.as_deref(), Some("auto"));
+        assert_eq!(compactions[0].summary.as_deref(), Some("Earlier work"));
+    }
+}
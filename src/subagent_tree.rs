@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::DateTime;
+use tokio::fs;
+
+use crate::compression::{read_transcript, session_dir};
+use crate::models::{SubagentNode, SubagentStatus, UsageTotals};
+use crate::session_meta::get_subagent_metas;
+use crate::state::AppState;
+use crate::storage::find_session_file;
+
+/// Tools that start a subagent (`Agent` in newer Claude Code versions).
+const TASK_TOOLS: &[&str] = &["Task", "Agent"];
+
+/// A Task call found in a transcript.
+#[derive(Default)]
+struct TaskCall {
+    tool_use_id: String,
+    description: Option<String>,
+    subagent_type: Option<String>,
+    started_at: Option<String>,
+    ended_at: Option<String>,
+    completed: bool,
+    is_error: bool,
+    /// From the result's `toolUseResult`
+    agent_id: Option<String>,
+    duration_ms: Option<i64>,
+}
+
+/// What one transcript (main or subagent) says about agents.
+#[derive(Default)]
+struct TranscriptScan {
+    tasks: Vec<TaskCall>,
+    tool_calls: BTreeMap<String, usize>,
+    last_timestamp: Option<String>,
+}
+
+fn duration_ms(start: Option<&str>, end: Option<&str>) -> Option<i64> {
+    let start = DateTime::parse_from_rfc3339(start?).ok()?;
+    let end = DateTime::parse_from_rfc3339(end?).ok()?;
+    Some((end - start).num_milliseconds())
+}
+
+/// Scan a transcript for Task calls and tool counts, and record the
+/// tool_use_id → agentId links of its `agent_progress` lines in `links`.
+fn scan_transcript(content: &str, links: &mut HashMap<String, String>) -> TranscriptScan {
+    let mut scan = TranscriptScan::default();
+    let mut tasks_by_id: HashMap<String, usize> = HashMap::new();
+    let mut seen_tool_uses = HashSet::new();
+
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let Ok(val) = serde_json::from_str::<serde_json::Value>(line) else { continue };
+        let str_field = |key: &str| val.get(key).and_then(|v| v.as_str()).map(String::from);
+        if let Some(ts) = str_field("timestamp") {
+            if scan.last_timestamp.as_ref().is_none_or(|last| ts > *last) {
+                scan.last_timestamp = Some(ts);
+            }
+        }
+
+        let blocks = val.pointer("/message/content").and_then(|c| c.as_array());
+        match val.get("type").and_then(|t| t.as_str()) {
+            Some("progress") => {
+                let agent_id = val.pointer("/data/agentId").and_then(|v| v.as_str());
+                let is_agent = val.pointer("/data/type").and_then(|v| v.as_str()) == Some("agent_progress");
+                if let (true, Some(agent_id), Some(tool_use_id)) = (is_agent, agent_id, str_field("parentToolUseID")) {
+                    links.entry(tool_use_id).or_insert_with(|| agent_id.to_string());
+                }
+            }
+            Some("assistant") => {
+                for block in blocks.into_iter().flatten() {
+                    if block.get("type").and_then(|t| t.as_str()) != Some("tool_use") {
+                        continue;
+                    }
+                    let (Some(id), Some(name)) = (
+                        block.get("id").and_then(|v| v.as_str()),
+                        block.get("name").and_then(|v| v.as_str()),
+                    ) else {
+                        continue;
+                    };
+                    // Claude Code writes one line per content block and may repeat them
+                    if !seen_tool_uses.insert(id.to_string()) {
+                        continue;
+                    }
+                    *scan.tool_calls.entry(name.to_string()).or_default() += 1;
+                    if TASK_TOOLS.contains(&name) {
+                        let input_field = |key: &str| block.pointer(&format!("/input/{}", key)).and_then(|v| v.as_str()).map(String::from);
+                        tasks_by_id.insert(id.to_string(), scan.tasks.len());
+                        scan.tasks.push(TaskCall {
+                            tool_use_id: id.to_string(),
+                            description: input_field("description"),
+                            subagent_type: input_field("subagent_type"),
+                            started_at: str_field("timestamp"),
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+            Some("user") => {
+                for block in blocks.into_iter().flatten() {
+                    if block.get("type").and_then(|t| t.as_str()) != Some("tool_result") {
+                        continue;
+                    }
+                    let Some(&i) = block.get("tool_use_id").and_then(|v| v.as_str()).and_then(|id| tasks_by_id.get(id)) else {
+                        continue;
+                    };
+                    let task = &mut scan.tasks[i];
+                    let result = val.get("toolUseResult");
+                    task.completed = true;
+                    task.is_error = block.get("is_error").and_then(|e| e.as_bool()) == Some(true);
+                    task.ended_at = str_field("timestamp");
+                    task.agent_id = result.and_then(|r| r.get("agentId")).and_then(|v| v.as_str()).map(String::from);
+                    task.duration_ms = result.and_then(|r| r.get("totalDurationMs")).and_then(|v| v.as_i64());
+                }
+            }
+            _ => {}
+        }
+    }
+    scan
+}
+
+/// Everything known about a session's agents, before building the tree.
+struct Scans {
+    root: TranscriptScan,
+    agents: HashMap<String, TranscriptScan>,
+    links: HashMap<String, String>,
+    usage: HashMap<String, UsageTotals>,
+}
+
+fn build_node(task: Option<&TaskCall>, agent_id: Option<String>, scans: &Scans, visited: &mut HashSet<String>) -> SubagentNode {
+    let agent_scan = agent_id.as_ref().and_then(|id| scans.agents.get(id));
+    let status = match task {
+        Some(t) if t.completed && t.is_error => SubagentStatus::Errored,
+        Some(t) if t.completed => SubagentStatus::Finished,
+        Some(_) => SubagentStatus::Running,
+        // No Task call to tell: an orphan transcript is over
+        None => SubagentStatus::Finished,
+    };
+    let started_at = task.and_then(|t| t.started_at.clone());
+    let ended_at = task.and_then(|t| t.ended_at.clone());
+    let last_seen = ended_at.clone().or_else(|| agent_scan.and_then(|s| s.last_timestamp.clone()));
+    let duration_ms = task
+        .and_then(|t| t.duration_ms)
+        .or_else(|| duration_ms(started_at.as_deref(), last_seen.as_deref()));
+
+    let mut children = Vec::new();
+    if let Some(id) = agent_id.as_ref().filter(|id| visited.insert(id.to_string())) {
+        if let Some(scan) = scans.agents.get(id) {
+            children = build_children(scan, scans, visited);
+        }
+    }
+
+    SubagentNode {
+        usage: agent_id.as_ref().and_then(|id| scans.usage.get(id)).cloned().unwrap_or_default(),
+        tool_calls: agent_scan.map(|s| s.tool_calls.clone()).unwrap_or_default(),
+        agent_id,
+        tool_use_id: task.map(|t| t.tool_use_id.clone()),
+        description: task.and_then(|t| t.description.clone()),
+        subagent_type: task.and_then(|t| t.subagent_type.clone()),
+        status,
+        started_at,
+        ended_at,
+        duration_ms,
+        children,
+    }
+}
+
+fn build_children(scan: &TranscriptScan, scans: &Scans, visited: &mut HashSet<String>) -> Vec<SubagentNode> {
+    scan.tasks
+        .iter()
+        .map(|task| {
+            let agent_id = task.agent_id.clone().or_else(|| scans.links.get(&task.tool_use_id).cloned());
+            build_node(Some(task), agent_id, scans, visited)
+        })
+        .collect()
+}
+
+/// The tree of agents a session ran: its Task calls, the agents those
+/// spawned, and so on. Subagent transcripts no Task call leads to are listed
+/// at the top level. None if the session doesn't exist.
+pub async fn get_subagent_tree(state: &AppState, session_id: &str) -> Option<Vec<SubagentNode>> {
+    let file_path = find_session_file(state, session_id).await?;
+    let content = read_transcript(&file_path).await.ok()?;
+
+    let mut links = HashMap::new();
+    let root = scan_transcript(&String::from_utf8_lossy(&content), &mut links);
+
+    // All subagents, whatever their depth, live in the session's subagents/
+    let mut agents = HashMap::new();
+    let subagents_dir = format!("{}/subagents", session_dir(&file_path));
+    if let Ok(mut entries) = fs::read_dir(&subagents_dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let Some(agent_id) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix("agent-")?.strip_suffix(".jsonl"))
+                .map(String::from)
+            else {
+                continue;
+            };
+            if let Ok(content) = fs::read_to_string(&path).await {
+                agents.insert(agent_id, scan_transcript(&content, &mut links));
+            }
+        }
+    }
+
+    let mut usage = HashMap::new();
+    for meta in get_subagent_metas(state, session_id).await {
+        let Some(agent_id) = std::path::Path::new(&meta.path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.strip_prefix("agent-"))
+        else {
+            continue;
+        };
+        let mut totals = UsageTotals::default();
+        for models in meta.usage.values() {
+            for model_totals in models.values() {
+                totals.add(model_totals);
+            }
+        }
+        usage.insert(agent_id.to_string(), totals);
+    }
+
+    let scans = Scans { root, agents, links, usage };
+    let mut visited = HashSet::new();
+    let mut tree = build_children(&scans.root, &scans, &mut visited);
+
+    let mut orphans: Vec<&String> = scans.agents.keys().filter(|id| !visited.contains(*id)).collect();
+    orphans.sort();
+    for agent_id in orphans {
+        if visited.contains(agent_id) {
+            continue;
+        }
+        tree.push(build_node(None, Some(agent_id.clone()), &scans, &mut visited));
+    }
+    Some(tree)
+}
@@ -8,20 +8,25 @@ use crate::storage::{count_session_messages, get_conversation};
 
 const SUMMARY_THRESHOLD: usize = 3;
 
-/// Directory for per-session summary files
-fn summary_dir(state: &AppState) -> String {
-    format!("{}/summary", state.claude_dir)
+/// Directory for per-session summary files, in the session's own source
+fn summary_dir(state: &AppState, session_id: &str) -> String {
+    format!("{}/summary", state.session_source(session_id).claude_dir)
 }
 
 /// Path to a single session's summary file
-fn summary_path(state: &AppState, session_id: &str) -> String {
-    format!("{}/{}", summary_dir(state), session_id)
+pub fn summary_path(state: &AppState, session_id: &str) -> String {
+    format!("{}/{}", summary_dir(state, session_id), session_id)
 }
 
-/// Load persisted summaries into the in-memory cache
+/// Load persisted summaries of every source into the in-memory cache
 pub async fn load_summaries(state: &AppState) {
-    let dir = summary_dir(state);
-    let mut entries = match fs::read_dir(&dir).await {
+    for source in &state.sources {
+        load_summary_dir(state, &format!("{}/summary", source.claude_dir)).await;
+    }
+}
+
+async fn load_summary_dir(state: &AppState, dir: &str) {
+    let mut entries = match fs::read_dir(dir).await {
         Ok(e) => e,
         Err(_) => return,
     };
@@ -47,7 +52,7 @@ pub async fn load_summaries(state: &AppState) {
 
 /// Persist a single session summary to disk
 async fn save_summary(state: &AppState, session_id: &str, summary: &str, msg_count: usize) {
-    let dir = summary_dir(state);
+    let dir = summary_dir(state, session_id);
     let _ = fs::create_dir_all(&dir).await;
     let content = format!("{}\n{}", msg_count, summary);
     let _ = fs::write(summary_path(state, session_id), content).await;
@@ -55,7 +60,7 @@ async fn save_summary(state: &AppState, session_id: &str, summary: &str, msg_cou
 
 /// Generate a summary for a session, preferring compaction summary over LLM call
 async fn generate_summary(state: &Arc<AppState>, session_id: &str) {
-    let messages = get_conversation(state, session_id).await;
+    let messages = get_conversation(state, session_id, false).await;
     if messages.is_empty() {
         return;
     }
@@ -179,7 +184,7 @@ async fn generate_summary(state: &Arc<AppState>, session_id: &str) {
 
 /// Extract a quick summary from the first user message (truncated, no LLM)
 async fn set_early_summary(state: &Arc<AppState>, session_id: &str) {
-    let messages = get_conversation(state, session_id).await;
+    let messages = get_conversation(state, session_id, false).await;
     let first_user = messages.iter().find(|m| m.msg_type == "user");
     let text = match first_user {
         Some(msg) => {
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use chrono::DateTime;
+
+use crate::models::{ConversationMessage, MessageContent, ToolCall};
+use crate::state::AppState;
+use crate::storage::get_conversation;
+
+/// Pairs assistant `tool_use` blocks with the user `tool_result` blocks that
+/// answer them, keyed by `tool_use_id`.
+#[derive(Default)]
+pub struct ToolCallTracker {
+    calls: Vec<ToolCall>,
+    by_id: HashMap<String, usize>,
+}
+
+fn duration_ms(start: Option<&str>, end: Option<&str>) -> Option<i64> {
+    let start = DateTime::parse_from_rfc3339(start?).ok()?;
+    let end = DateTime::parse_from_rfc3339(end?).ok()?;
+    Some((end - start).num_milliseconds())
+}
+
+impl ToolCallTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entry(&mut self, tool_use_id: &str) -> usize {
+        if let Some(&i) = self.by_id.get(tool_use_id) {
+            return i;
+        }
+        self.calls.push(ToolCall {
+            tool_use_id: tool_use_id.to_string(),
+            name: None,
+            input: None,
+            result: None,
+            is_error: false,
+            completed: false,
+            started_at: None,
+            ended_at: None,
+            duration_ms: None,
+            message_uuid: None,
+            result_uuid: None,
+        });
+        self.by_id.insert(tool_use_id.to_string(), self.calls.len() - 1);
+        self.calls.len() - 1
+    }
+
+    /// Record the tool blocks of one message. Returns the indexes of the calls
+    /// that were created or changed.
+    pub fn observe(&mut self, msg: &ConversationMessage) -> Vec<usize> {
+        let blocks = match msg.message.as_ref().and_then(|m| m.content.as_ref()) {
+            Some(MessageContent::Blocks(blocks)) => blocks,
+            _ => return Vec::new(),
+        };
+
+        let mut changed = Vec::new();
+        for block in blocks {
+            match block.block_type.as_str() {
+                "tool_use" if msg.msg_type == "assistant" => {
+                    let Some(id) = block.id.as_deref() else { continue };
+                    let i = self.entry(id);
+                    let call = &mut self.calls[i];
+                    call.name = block.name.clone();
+                    call.input = block.input.clone();
+                    call.started_at = msg.timestamp.clone();
+                    call.message_uuid = msg.uuid.clone();
+                    call.duration_ms = duration_ms(call.started_at.as_deref(), call.ended_at.as_deref());
+                    changed.push(i);
+                }
+                "tool_result" if msg.msg_type == "user" => {
+                    let Some(id) = block.tool_use_id.as_deref() else { continue };
+                    let i = self.entry(id);
+                    let call = &mut self.calls[i];
+                    call.result = block.content.clone();
+                    call.is_error = block.is_error.unwrap_or(false);
+                    call.completed = true;
+                    call.ended_at = msg.timestamp.clone();
+                    call.result_uuid = msg.uuid.clone();
+                    call.duration_ms = duration_ms(call.started_at.as_deref(), call.ended_at.as_deref());
+                    changed.push(i);
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+
+    pub fn get(&self, i: usize) -> &ToolCall {
+        &self.calls[i]
+    }
+
+    pub fn into_calls(self) -> Vec<ToolCall> {
+        self.calls
+    }
+}
+
+/// All tool calls of a session in call order.
+pub async fn get_tool_calls(state: &AppState, session_id: &str) -> Vec<ToolCall> {
+    let mut tracker = ToolCallTracker::new();
+    for msg in get_conversation(state, session_id, false).await {
+        tracker.observe(&msg);
+    }
+    tracker.into_calls()
+}
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::compression::session_dir;
+use crate::models::{HistoryEntry, TrashedSession};
+use crate::session_meta::{forget_meta, get_session_meta};
+use crate::state::AppState;
+use crate::storage::{find_session_file, get_project_name, project_path_from_file, write_atomic};
+use crate::summarizer::summary_path;
+use crate::user_meta;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// What `delete_session` removed, kept so the session can be restored.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrashRecord {
+    session_id: String,
+    /// ms since the epoch
+    deleted_at: f64,
+    /// Raw history.jsonl lines removed on delete
+    history: Vec<String>,
+}
+
+fn trash_dir(state: &AppState) -> String {
+    format!("{}/trash", state.claude_dir)
+}
+
+fn record_path(state: &AppState, session_id: &str) -> String {
+    format!("{}/{}.json", trash_dir(state), session_id)
+}
+
+fn now_ms() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+/// Remember a deleted session's history lines so it can be restored.
+pub async fn save_record(state: &AppState, session_id: &str, history: Vec<String>) {
+    let record = TrashRecord {
+        session_id: session_id.to_string(),
+        deleted_at: now_ms(),
+        history,
+    };
+    let _ = fs::create_dir_all(trash_dir(state)).await;
+    if let Ok(json) = serde_json::to_vec(&record) {
+        let _ = fs::write(record_path(state, session_id), json).await;
+    }
+}
+
+async fn load_record(state: &AppState, session_id: &str) -> Option<TrashRecord> {
+    let content = fs::read(record_path(state, session_id)).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// Stop hiding a session, here and in the persisted `deleted_sessions` list.
+async fn unhide(state: &AppState, session_id: &str) -> Result<(), String> {
+    let deleted_path = format!("{}/deleted_sessions", state.claude_dir);
+    if let Ok(content) = fs::read_to_string(&deleted_path).await {
+        let remaining: String = content
+            .lines()
+            .filter(|l| !l.trim().is_empty() && l.trim() != session_id)
+            .map(|l| format!("{}\n", l))
+            .collect();
+        write_atomic(&deleted_path, remaining.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to update deleted_sessions: {}", e))?;
+    }
+    state.hidden_sessions.remove(session_id);
+    let _ = fs::remove_file(record_path(state, session_id)).await;
+    Ok(())
+}
+
+/// Deleted sessions, most recently deleted first.
+pub async fn list_trash(state: &AppState) -> Vec<TrashedSession> {
+    let ids: Vec<String> = state.hidden_sessions.iter().map(|e| e.key().clone()).collect();
+    let mut sessions = Vec::new();
+    for id in ids {
+        let record = load_record(state, &id).await;
+        let history: Option<HistoryEntry> = record
+            .as_ref()
+            .and_then(|r| r.history.first())
+            .and_then(|line| serde_json::from_str(line).ok());
+        let meta = get_session_meta(state, &id).await;
+
+        let project = history
+            .as_ref()
+            .map(|h| h.project.clone())
+            .filter(|p| !p.is_empty())
+            .or_else(|| meta.as_ref().and_then(|m| m.cwd.clone()))
+            .or_else(|| meta.as_ref().map(|m| project_path_from_file(&m.path)))
+            .unwrap_or_default();
+        let display = history
+            .map(|h| h.display)
+            .or_else(|| meta.as_ref().and_then(|m| m.first_prompt.clone()))
+            .unwrap_or_else(|| id.clone());
+
+        sessions.push(TrashedSession {
+            display,
+            project_name: get_project_name(&project),
+            project,
+            deleted_at: record.map(|r| r.deleted_at),
+            message_count: meta.as_ref().map(|m| m.message_count).unwrap_or(0),
+            file_size: meta.map(|m| m.file_size),
+            id,
+        });
+    }
+    sessions.sort_by(|a, b| {
+        b.deleted_at
+            .unwrap_or(0.0)
+            .partial_cmp(&a.deleted_at.unwrap_or(0.0))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    sessions
+}
+
+fn history_timestamp(line: &str) -> Option<f64> {
+    serde_json::from_str::<HistoryEntry>(line).ok().map(|e| e.timestamp)
+}
+
+/// Merge restored history lines back into history.jsonl by timestamp, keeping
+/// the file in chronological order. Unparseable lines stay where they are.
+fn merge_history(existing: &str, restored: &[String]) -> String {
+    let mut restored: Vec<(f64, &str)> = restored
+        .iter()
+        .map(|line| (history_timestamp(line).unwrap_or(0.0), line.as_str()))
+        .collect();
+    restored.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    let mut restored = restored.into_iter().peekable();
+
+    let mut content = String::with_capacity(existing.len() + restored.len() * 256);
+    for line in existing.lines().filter(|l| !l.trim().is_empty()) {
+        if let Some(ts) = history_timestamp(line) {
+            while let Some((_, restored_line)) = restored.next_if(|(restored_ts, _)| *restored_ts <= ts) {
+                content.push_str(restored_line);
+                content.push('\n');
+            }
+        }
+        content.push_str(line);
+        content.push('\n');
+    }
+    for (_, line) in restored {
+        content.push_str(line);
+        content.push('\n');
+    }
+    content
+}
+
+/// Bring a deleted session back: re-add its history entries and unhide it.
+pub async fn restore_session(state: &AppState, session_id: &str) -> Result<(), String> {
+    if !state.hidden_sessions.contains_key(session_id) {
+        return Err("Session not in trash".to_string());
+    }
+
+    // Locate the transcript first so the history goes back to its own source
+    find_session_file(state, session_id).await;
+    if let Some(record) = load_record(state, session_id).await {
+        if !record.history.is_empty() {
+            let history_path = format!("{}/history.jsonl", state.session_source(session_id).claude_dir);
+            let existing = fs::read_to_string(&history_path).await.unwrap_or_default();
+            let content = merge_history(&existing, &record.history);
+            write_atomic(&history_path, content.as_bytes())
+                .await
+                .map_err(|e| format!("Failed to write history.jsonl: {}", e))?;
+        }
+    }
+
+    unhide(state, session_id).await?;
+    state.invalidate_history_cache();
+    let _ = state.history_tx.send(());
+    Ok(())
+}
+
+/// Remove a session's transcript, subagent directory, summary and cached
+/// metadata from disk and memory.
+pub async fn remove_session_data(state: &AppState, session_id: &str) -> Result<(), String> {
+    if let Some(file_path) = find_session_file(state, session_id).await {
+        fs::remove_file(&file_path)
+            .await
+            .map_err(|e| format!("Failed to remove {}: {}", file_path, e))?;
+        let session_dir = session_dir(&file_path);
+        if fs::metadata(session_dir).await.is_ok_and(|m| m.is_dir()) {
+            let _ = fs::remove_dir_all(session_dir).await;
+        }
+    }
+    let _ = fs::remove_file(summary_path(state, session_id)).await;
+
+    state.file_index.remove(session_id);
+    state.summary_cache.remove(session_id);
+    user_meta::remove_user_meta(state, session_id).await;
+    let subagent_prefix = format!("{}/", session_id);
+    let keys: Vec<String> = state
+        .session_meta
+        .iter()
+        .filter(|e| e.key() == session_id || e.key().starts_with(&subagent_prefix))
+        .map(|e| e.key().clone())
+        .collect();
+    for key in keys {
+        forget_meta(state, &key);
+    }
+    Ok(())
+}
+
+/// Permanently remove a deleted session.
+pub async fn purge_session(state: &AppState, session_id: &str) -> Result<(), String> {
+    if !state.hidden_sessions.contains_key(session_id) {
+        return Err("Session not in trash".to_string());
+    }
+
+    remove_session_data(state, session_id).await?;
+    unhide(state, session_id).await?;
+    let _ = state.history_tx.send(());
+    eprintln!("[trash] purged {}", session_id);
+    Ok(())
+}
+
+/// Purge sessions that have been in the trash longer than `retention`.
+/// Sessions deleted before the trash existed start their clock now.
+pub fn spawn_trash_purger(state: Arc<AppState>, retention: Duration) {
+    tokio::spawn(async move {
+        loop {
+            let ids: Vec<String> = state.hidden_sessions.iter().map(|e| e.key().clone()).collect();
+            let cutoff = now_ms() - retention.as_secs_f64() * 1000.0;
+            for id in ids {
+                match load_record(&state, &id).await {
+                    Some(record) if record.deleted_at < cutoff => {
+                        if let Err(e) = purge_session(&state, &id).await {
+                            eprintln!("[trash] failed to purge {}: {}", id, e);
+                        }
+                    }
+                    Some(_) => {}
+                    None => save_record(&state, &id, Vec::new()).await,
+                }
+            }
+            tokio::time::sleep(PURGE_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(display: &str, timestamp: u64) -> String {
+        format!(r#"{{"display":"{}","timestamp":{},"project":"/p","sessionId":"s"}}"#, display, timestamp)
+    }
+
+    fn displays(content: &str) -> Vec<String> {
+        content
+            .lines()
+            .map(|l| {
+                serde_json::from_str::<HistoryEntry>(l)
+                    .map(|e| e.display)
+                    .unwrap_or_else(|_| l.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merge_history_interleaves_by_timestamp() {
+        let existing = [entry("a", 10), entry("c", 30), entry("e", 50)].join("\n") + "\n";
+        let restored = vec![entry("f", 60), entry("b", 20), entry("d", 40)];
+        let merged = merge_history(&existing, &restored);
+        assert_eq!(displays(&merged), ["a", "b", "c", "d", "e", "f"]);
+    }
+
+    #[test]
+    fn merge_history_puts_restored_lines_first_on_equal_timestamps() {
+        let existing = [entry("old", 10), entry("later", 20)].join("\n");
+        let restored = vec![entry("restored", 10)];
+        let merged = merge_history(&existing, &restored);
+        assert_eq!(displays(&merged), ["restored", "old", "later"]);
+    }
+
+    #[test]
+    fn merge_history_keeps_unparseable_lines_in_place() {
+        let existing = [entry("a", 10), "garbage".to_string(), String::new(), entry("c", 30)].join("\n");
+        let restored = vec![entry("b", 20), "broken".to_string()];
+        let merged = merge_history(&existing, &restored);
+        // Unparseable restored lines sort as timestamp 0, ahead of everything
+        assert_eq!(displays(&merged), ["broken", "a", "garbage", "b", "c"]);
+    }
+}
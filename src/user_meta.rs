@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use tokio::fs;
+
+use crate::models::SessionUserMeta;
+use crate::state::AppState;
+use crate::storage::write_atomic;
+
+fn user_meta_path(state: &AppState) -> String {
+    format!("{}/session-user-meta.json", state.claude_dir)
+}
+
+/// Load stars, tags, titles and notes saved by the user.
+pub async fn load_user_meta(state: &AppState) {
+    let content = match fs::read(user_meta_path(state)).await {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    match serde_json::from_slice::<HashMap<String, SessionUserMeta>>(&content) {
+        Ok(entries) => {
+            for (session_id, meta) in entries {
+                state.user_meta.insert(session_id, meta);
+            }
+        }
+        Err(e) => eprintln!("[user_meta] invalid {}: {}", user_meta_path(state), e),
+    }
+}
+
+async fn save_user_meta(state: &AppState) -> Result<(), String> {
+    let entries: BTreeMap<String, SessionUserMeta> = state
+        .user_meta
+        .iter()
+        .map(|e| (e.key().clone(), e.value().clone()))
+        .collect();
+    let json = serde_json::to_vec_pretty(&entries).map_err(|e| e.to_string())?;
+    write_atomic(&user_meta_path(state), &json)
+        .await
+        .map_err(|e| format!("Failed to save session metadata: {}", e))
+}
+
+/// Partial update: fields left out are unchanged, an empty title or notes clears it.
+#[derive(Debug, Deserialize)]
+pub struct UserMetaUpdate {
+    pub starred: Option<bool>,
+    pub pinned: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    pub title: Option<String>,
+    pub notes: Option<String>,
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let trimmed = s.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+/// Tags are trimmed and de-duplicated, keeping their first-seen order.
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !out.iter().any(|t| t == tag) {
+            out.push(tag.to_string());
+        }
+    }
+    out
+}
+
+pub fn get_user_meta(state: &AppState, session_id: &str) -> SessionUserMeta {
+    state
+        .user_meta
+        .get(session_id)
+        .map(|m| m.value().clone())
+        .unwrap_or_default()
+}
+
+pub async fn update_user_meta(
+    state: &AppState,
+    session_id: &str,
+    update: UserMetaUpdate,
+) -> Result<SessionUserMeta, String> {
+    let mut meta = get_user_meta(state, session_id);
+    if let Some(starred) = update.starred {
+        meta.starred = starred;
+    }
+    if let Some(pinned) = update.pinned {
+        meta.pinned = pinned;
+    }
+    if let Some(tags) = update.tags {
+        meta.tags = normalize_tags(tags);
+    }
+    if let Some(title) = update.title {
+        meta.title = non_empty(title);
+    }
+    if let Some(notes) = update.notes {
+        meta.notes = non_empty(notes);
+    }
+    meta.updated_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0);
+
+    if meta.is_empty() {
+        state.user_meta.remove(session_id);
+    } else {
+        state.user_meta.insert(session_id.to_string(), meta.clone());
+    }
+    save_user_meta(state).await?;
+    // Re-send the session list so other clients see the change
+    let _ = state.history_tx.send(());
+    Ok(meta)
+}
+
+pub async fn remove_user_meta(state: &AppState, session_id: &str) {
+    if state.user_meta.remove(session_id).is_some() {
+        if let Err(e) = save_user_meta(state).await {
+            eprintln!("[user_meta] {}", e);
+        }
+    }
+}
+
+/// Every tag in use with the number of sessions carrying it, most used first.
+pub fn list_tags(state: &AppState) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for entry in state.user_meta.iter() {
+        for tag in &entry.value().tags {
+            *counts.entry(tag.clone()).or_default() += 1;
+        }
+    }
+    let mut tags: Vec<(String, usize)> = counts.into_iter().collect();
+    tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    tags
+}
@@ -7,9 +7,26 @@ use tokio::sync::mpsc;
 
 use crate::state::AppState;
 
+/// Watch each Claude dir (catches history.jsonl changes) and its projects dir.
+fn watch_dirs<W: Watcher>(watcher: &mut W, dirs: &[(PathBuf, PathBuf)]) {
+    for (claude_dir, projects_dir) in dirs {
+        if let Err(e) = watcher.watch(claude_dir, RecursiveMode::NonRecursive) {
+            eprintln!("[watcher] failed to watch claude dir {}: {}", claude_dir.display(), e);
+        }
+        if projects_dir.exists() {
+            if let Err(e) = watcher.watch(projects_dir, RecursiveMode::Recursive) {
+                eprintln!("[watcher] failed to watch projects dir {}: {}", projects_dir.display(), e);
+            }
+        }
+    }
+}
+
 pub async fn start_watcher(state: Arc<AppState>) -> anyhow::Result<()> {
-    let claude_dir = PathBuf::from(&state.claude_dir);
-    let projects_dir = PathBuf::from(&state.projects_dir);
+    let dirs: Vec<(PathBuf, PathBuf)> = state
+        .sources
+        .iter()
+        .map(|s| (PathBuf::from(&s.claude_dir), PathBuf::from(&s.projects_dir)))
+        .collect();
 
     let (tx, mut rx) = mpsc::channel::<Event>(256);
 
@@ -18,8 +35,6 @@ pub async fn start_watcher(state: Arc<AppState>) -> anyhow::Result<()> {
         .unwrap_or(false);
 
     // Spawn the watcher in a blocking thread since notify watchers are !Send on some platforms
-    let claude_dir_clone = claude_dir.clone();
-    let projects_dir_clone = projects_dir.clone();
     let dev_mode = state.dev_mode;
 
     std::thread::spawn(move || {
@@ -38,15 +53,7 @@ pub async fn start_watcher(state: Arc<AppState>) -> anyhow::Result<()> {
             )
             .expect("Failed to create poll watcher");
 
-            // Watch ~/.claude/ directory (catches history.jsonl changes)
-            if let Err(e) = watcher.watch(&claude_dir_clone, RecursiveMode::NonRecursive) {
-                eprintln!("[watcher] failed to watch claude dir: {}", e);
-            }
-            if projects_dir_clone.exists() {
-                if let Err(e) = watcher.watch(&projects_dir_clone, RecursiveMode::Recursive) {
-                    eprintln!("[watcher] failed to watch projects dir: {}", e);
-                }
-            }
+            watch_dirs(&mut watcher, &dirs);
 
             loop {
                 std::thread::sleep(Duration::from_secs(3600));
@@ -63,18 +70,12 @@ pub async fn start_watcher(state: Arc<AppState>) -> anyhow::Result<()> {
             )
             .expect("Failed to create watcher");
 
-            // Watch ~/.claude/ directory (catches history.jsonl changes)
-            if let Err(e) = watcher.watch(&claude_dir_clone, RecursiveMode::NonRecursive) {
-                eprintln!("[watcher] failed to watch claude dir: {}", e);
-            }
-            if projects_dir_clone.exists() {
-                if let Err(e) = watcher.watch(&projects_dir_clone, RecursiveMode::Recursive) {
-                    eprintln!("[watcher] failed to watch projects dir: {}", e);
-                }
-            }
+            watch_dirs(&mut watcher, &dirs);
 
             if dev_mode {
-                eprintln!("[watcher] watching {} and {}", claude_dir_clone.display(), projects_dir_clone.display());
+                for (claude_dir, projects_dir) in &dirs {
+                    eprintln!("[watcher] watching {} and {}", claude_dir.display(), projects_dir.display());
+                }
             }
 
             loop {
@@ -150,14 +151,30 @@ fn emit_change(state: &AppState, path: &Path) {
         }
         state.invalidate_history_cache();
         let _ = state.history_tx.send(());
-    } else if path_str.ends_with(".jsonl") && !path_str.contains("/subagents/") {
+    } else if path_str.ends_with(".jsonl") && path_str.contains("/subagents/") {
+        // <sessionId>/subagents/agent-<agentId>.jsonl
+        let session_id = path.parent().and_then(|p| p.parent()).and_then(|p| p.file_name());
+        let agent_id = path.file_stem().and_then(|s| s.to_string_lossy().strip_prefix("agent-").map(String::from));
+        if let (Some(session_id), Some(agent_id)) = (session_id, agent_id) {
+            if state.dev_mode {
+                eprintln!("[watcher] subagent file: {}/{}", session_id.to_string_lossy(), agent_id);
+            }
+            let _ = state.subagent_tx.send((session_id.to_string_lossy().to_string(), agent_id));
+        }
+    } else if path_str.ends_with(".jsonl") {
         if let Some(stem) = path.file_stem() {
             let session_id = stem.to_string_lossy().to_string();
             let file_path = path.to_string_lossy().to_string();
+            // Removed because retention compressed it: the index already
+            // points at the compressed file
+            if !path.exists() && state.file_index.get(&session_id).is_some_and(|p| *p != file_path) {
+                return;
+            }
             if state.dev_mode {
                 eprintln!("[watcher] session file: {}", session_id);
             }
             state.file_index.insert(session_id.clone(), file_path.clone());
+            state.search_index.schedule(&session_id, &file_path);
             let _ = state.session_tx.send((session_id, file_path));
         }
     }
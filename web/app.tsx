@@ -1,5 +1,5 @@
 import { useState, useEffect, useCallback, useMemo, useRef } from "react";
-import type { Session } from "@claude-run/api";
+import type { Session, SourceInfo } from "@claude-run/api";
 import { PanelLeft, Plus, X, Bell, BellPlus, Square, Trash2, Loader2, ExternalLink, Sun, Moon, FolderOpen } from "lucide-react";
 import { formatTime } from "./utils";
 import SessionList from "./components/session-list";
@@ -131,7 +131,7 @@ function AttentionIndicator({ sessions, onNavigate }: { sessions: AttentionSessi
                   ) : (
                     <span className="w-1.5 h-1.5 bg-green-500 rounded-full shrink-0" />
                   )}
-                  <span className="text-[11px] text-foreground truncate">{s.summary || s.display}</span>
+                  <span className="text-[11px] text-foreground truncate">{s.title || s.summary || s.display}</span>
                 </div>
                 {s.projectName && (
                   <p className="text-[10px] text-zinc-600 truncate mt-0.5 ml-3.5">{s.projectName}</p>
@@ -446,6 +446,8 @@ function App() {
   const [showLaunchModal, setShowLaunchModal] = useState(false);
   const [launchProject, setLaunchProject] = useState("");
   const [launchPrompt, setLaunchPrompt] = useState("");
+  const [launchSource, setLaunchSource] = useState("");
+  const [sources, setSources] = useState<SourceInfo[]>([]);
   const [skipPermissions, setSkipPermissions] = useState(true);
   const [zellijSession, setZellijSession] = useState("");
   const [pendingUrls, setPendingUrls] = useState<string[]>([]);
@@ -534,6 +536,11 @@ function App() {
     });
   }, []);
 
+  const handleSessionsRemoved = useCallback((event: MessageEvent) => {
+    const removed = new Set<string>(JSON.parse(event.data));
+    setSessions((prev) => prev.filter((s) => !removed.has(s.id)));
+  }, []);
+
   const handleStatusUpdate = useCallback((event: MessageEvent) => {
     const data = JSON.parse(event.data);
     setSessions((prev) => {
@@ -558,6 +565,7 @@ function App() {
     events: [
       { eventName: "sessions", onMessage: handleSessionsFull },
       { eventName: "sessionsUpdate", onMessage: handleSessionsUpdate },
+      { eventName: "sessionsRemoved", onMessage: handleSessionsRemoved },
       { eventName: "statusUpdate", onMessage: handleStatusUpdate },
       { eventName: "openUrl", onMessage: handleOpenUrl },
     ],
@@ -702,6 +710,7 @@ function App() {
           prompt: launchPrompt || undefined,
           dangerouslySkipPermissions: skipPermissions || undefined,
           zellijSession: zellijSession || newZellijName.trim() || undefined,
+          source: launchSource || undefined,
         }),
       });
       const data = await res.json();
@@ -715,7 +724,7 @@ function App() {
     } finally {
       setLaunching(false);
     }
-  }, [launchProject, zellijSession, skipPermissions, launchPrompt, newZellijName]);
+  }, [launchProject, zellijSession, skipPermissions, launchPrompt, newZellijName, launchSource]);
 
   return (
     <div className="flex flex-col h-full bg-background text-foreground">
@@ -765,6 +774,10 @@ function App() {
               onClick={() => {
                 setLaunchProject(projects[0] || "");
                 setShowLaunchModal(true);
+                fetch("/api/sources").then(r => r.json()).then((d: SourceInfo[]) => {
+                  setSources(d);
+                  if (!launchSource && d.length > 0) setLaunchSource(d[0].label);
+                }).catch(() => {});
                 fetch("/api/zellij/sessions").then(r => r.json()).then(d => {
                   const sessions = d.sessions || [];
                   setZellijSessions(sessions);
@@ -813,7 +826,7 @@ function App() {
           {selectedSessionData && (
             <div className="flex items-center gap-1.5 mt-1 pl-8">
               <span className="text-[11px] text-muted-foreground truncate flex-1">
-                {selectedSessionData.summary || selectedSessionData.display}
+                {selectedSessionData.title || selectedSessionData.summary || selectedSessionData.display}
               </span>
               <button
                 onClick={() => setOpenFile({ filePath: "", project: selectedSessionData.project, browse: true })}
@@ -872,7 +885,7 @@ function App() {
             )}
           </div>
           {openFile && (
-            <FilePanel filePath={openFile.filePath} project={openFile.project} browse={openFile.browse} onClose={() => setOpenFile(null)} onInsertRef={(ref) => { setPendingInsert(ref); setOpenFile(null); }} />
+            <FilePanel filePath={openFile.filePath} project={openFile.project} browse={openFile.browse} onClose={() => setOpenFile(null)} onInsertRef={(ref) => { setPendingInsert(ref); setOpenFile(null); }} onNavigateSession={handleSelectSession} />
           )}
         </div>
       </main>
@@ -974,6 +987,21 @@ function App() {
                   })}
                 </select>
               </div>
+              {sources.length > 1 && (
+                <div>
+                  <label htmlFor="launch-source" className="block text-xs text-muted-foreground mb-1.5">Claude directory</label>
+                  <select
+                    id="launch-source"
+                    value={launchSource}
+                    onChange={(e) => setLaunchSource(e.target.value)}
+                    className="w-full bg-muted border border-border rounded px-3 py-2 text-sm text-foreground focus:outline-none focus:border-ring"
+                  >
+                    {sources.map((s) => (
+                      <option key={s.label} value={s.label}>{s.label} ({s.claudeDir})</option>
+                    ))}
+                  </select>
+                </div>
+              )}
               <div>
                 <label htmlFor="launch-prompt" className="block text-xs text-muted-foreground mb-1.5">Initial prompt (optional)</label>
                 <textarea
@@ -1,5 +1,5 @@
 import React, { useState, useEffect, useMemo, useCallback, useRef } from "react";
-import { X, FileCode, Loader2, AlertCircle, ChevronRight, Folder, ArrowLeft, FolderOpen, MessageSquarePlus, ChevronUp, ChevronDown, Search, WrapText } from "lucide-react";
+import { X, FileCode, Loader2, AlertCircle, ChevronRight, Folder, ArrowLeft, FolderOpen, MessageSquarePlus, ChevronUp, ChevronDown, Search, WrapText, History } from "lucide-react";
 
 interface FilePanelProps {
   filePath: string;
@@ -7,6 +7,16 @@ interface FilePanelProps {
   browse?: boolean;
   onClose: () => void;
   onInsertRef?: (ref: string) => void;
+  onNavigateSession?: (sessionId: string) => void;
+}
+
+interface FileSession {
+  sessionId: string;
+  display: string;
+  projectName: string;
+  timestamp: number;
+  modified: boolean;
+  refs: { tool: string; access: "read" | "write" | "bash"; agentId?: string }[];
 }
 
 interface DirEntry {
@@ -809,11 +819,52 @@ function FileView({ filePath, project, onInsertRef, scrollRef }: { filePath: str
   );
 }
 
-export function FilePanel({ filePath, project, browse, onClose, onInsertRef }: FilePanelProps) {
+function FileSessions({ filePath, onNavigateSession }: { filePath: string; onNavigateSession: (sessionId: string) => void }) {
+  const [sessions, setSessions] = useState<FileSession[] | null>(null);
+
+  useEffect(() => {
+    setSessions(null);
+    fetch(`/api/files/sessions?path=${encodeURIComponent(filePath)}`)
+      .then((r) => r.json())
+      .then((data) => setSessions(data.sessions ?? []))
+      .catch(() => setSessions([]));
+  }, [filePath]);
+
+  if (sessions === null) {
+    return (
+      <div className="flex items-center gap-2 px-3 py-2 text-xs text-muted-foreground border-b border-border">
+        <Loader2 size={12} className="animate-spin" /> Finding sessions…
+      </div>
+    );
+  }
+  if (sessions.length === 0) {
+    return <div className="px-3 py-2 text-xs text-muted-foreground border-b border-border">No session touched this file</div>;
+  }
+  return (
+    <div className="max-h-48 overflow-auto border-b border-border">
+      {sessions.map((s) => (
+        <button
+          key={s.sessionId}
+          onClick={() => onNavigateSession(s.sessionId)}
+          className="w-full flex items-center gap-2 px-3 py-1.5 text-left text-xs hover:bg-muted transition-colors cursor-pointer"
+        >
+          <span className="flex-1 truncate text-foreground">{s.display}</span>
+          <span className={`shrink-0 ${s.modified ? "text-amber-600 dark:text-amber-400" : "text-muted-foreground/60"}`}>
+            {s.modified ? "modified" : "read"}
+          </span>
+          <span className="shrink-0 text-muted-foreground/60">{s.refs.length}×</span>
+        </button>
+      ))}
+    </div>
+  );
+}
+
+export function FilePanel({ filePath, project, browse, onClose, onInsertRef, onNavigateSession }: FilePanelProps) {
   const scrollContainerRef = useRef<HTMLDivElement>(null);
   const [mode, setMode] = useState<Mode>(() =>
     browse ? { type: "browse", dirPath: project } : { type: "file", filePath }
   );
+  const [showSessions, setShowSessions] = useState(false);
 
   // Reset mode when props change
   useEffect(() => {
@@ -858,6 +909,15 @@ export function FilePanel({ filePath, project, browse, onClose, onInsertRef }: F
             <FileCode size={14} className="text-muted-foreground shrink-0" />
           )}
           <Breadcrumbs path={headerPath} project={project} onNavigate={handleNavigate} />
+          {mode.type === "file" && onNavigateSession && (
+            <button
+              onClick={() => setShowSessions(!showSessions)}
+              className={`p-1 rounded transition-colors cursor-pointer shrink-0 ${showSessions ? "bg-muted text-foreground" : "hover:bg-muted text-muted-foreground"}`}
+              title="Sessions that touched this file"
+            >
+              <History size={14} />
+            </button>
+          )}
           <button
             onClick={onClose}
             className="p-1 hover:bg-muted rounded transition-colors cursor-pointer shrink-0"
@@ -866,6 +926,10 @@ export function FilePanel({ filePath, project, browse, onClose, onInsertRef }: F
           </button>
         </div>
 
+        {mode.type === "file" && onNavigateSession && showSessions && (
+          <FileSessions filePath={mode.filePath} onNavigateSession={onNavigateSession} />
+        )}
+
         {/* Content */}
         <div ref={scrollContainerRef} className="flex-1 overflow-auto min-h-0">
           {mode.type === "browse" ? (
@@ -116,9 +116,19 @@ function SessionItem({
         </span>
       </div>
       <p className={`text-[12px] leading-snug line-clamp-2 break-words ${isChild ? "text-muted-foreground line-clamp-1" : "text-foreground"}`}>
-        {session.summary || session.display}
+        {session.title || session.summary || session.display}
       </p>
       <div className="flex items-center gap-1.5 mt-1">
+        {session.host && (
+          <span className="px-1 text-[10px] rounded text-sky-600 bg-sky-600/10" title="claude-run peer">
+            {session.host}
+          </span>
+        )}
+        {session.source && (
+          <span className="px-1 text-[10px] rounded text-muted-foreground bg-muted" title="Claude directory">
+            {session.source}
+          </span>
+        )}
         {(session.zellijSession || paneId) && (
           <span className={`px-1 text-[10px] rounded ${paneVerified ? "text-green-600 bg-green-600/10" : "text-muted-foreground/60 bg-muted opacity-50"}`}>
             {session.zellijSession && paneId
@@ -1,4 +1,4 @@
-import { useState, useCallback } from "react";
+import { useState, useCallback, useEffect, useRef } from "react";
 import type { ConversationMessage } from "@claude-run/api";
 import { Bot, Play, Pause, ArrowRight, RefreshCw, ChevronDown, ChevronRight, MessageSquareText } from "lucide-react";
 import MessageBlock from "../message-block";
@@ -46,6 +46,33 @@ export function TaskRenderer(props: TaskRendererProps) {
   const [showConversation, setShowConversation] = useState(false);
   const [subMessages, setSubMessages] = useState<ConversationMessage[] | null>(null);
   const [loadingMessages, setLoadingMessages] = useState(false);
+  const [retry, setRetry] = useState(0);
+  const offsetRef = useRef(0);
+  const running = !status;
+
+  // Follow a running agent's transcript as it grows
+  useEffect(() => {
+    if (!showConversation || !running || !sessionId || !agentId) return;
+
+    const eventSource = new EventSource(
+      `/api/conversation/${sessionId}/subagents/${agentId}/stream?offset=${offsetRef.current}`
+    );
+    eventSource.addEventListener("messages", (event) => {
+      const data = JSON.parse(event.data) as { messages: ConversationMessage[]; offset: number };
+      offsetRef.current = data.offset;
+      setSubMessages((prev) => [...(prev ?? []), ...data.messages]);
+      setLoadingMessages(false);
+    });
+    let retryTimeout: ReturnType<typeof setTimeout> | undefined;
+    eventSource.onerror = () => {
+      eventSource.close();
+      retryTimeout = setTimeout(() => setRetry((n) => n + 1), 2000);
+    };
+    return () => {
+      eventSource.close();
+      clearTimeout(retryTimeout);
+    };
+  }, [showConversation, running, sessionId, agentId, retry]);
 
   const toggleConversation = useCallback(() => {
     if (!sessionId || !agentId) return;
@@ -55,7 +82,8 @@ export function TaskRenderer(props: TaskRendererProps) {
       return;
     }
 
-    if (subMessages !== null) {
+    if (subMessages !== null || running) {
+      setLoadingMessages(subMessages === null);
       setShowConversation(true);
       return;
     }
@@ -73,7 +101,7 @@ export function TaskRenderer(props: TaskRendererProps) {
         setSubMessages([]);
         setLoadingMessages(false);
       });
-  }, [sessionId, agentId, showConversation, subMessages]);
+  }, [sessionId, agentId, showConversation, subMessages, running]);
 
   if (!input) {
     return null;
@@ -18,6 +18,27 @@ export interface Session {
   gitBranch?: string;
   summary?: string;
   fileSize?: number;
+  starred: boolean;
+  pinned: boolean;
+  tags?: string[];
+  title?: string;
+  notes?: string;
+  source?: string;
+  host?: string;
+}
+
+export interface SourceInfo {
+  label: string;
+  claudeDir: string;
+}
+
+export interface SessionUserMeta {
+  starred: boolean;
+  pinned: boolean;
+  tags: string[];
+  title?: string;
+  notes?: string;
+  updatedAt: number;
 }
 
 export interface ConversationMessage {
@@ -70,10 +91,108 @@ export interface SearchResult {
   projectName: string;
   timestamp: number;
   matches: SearchMatch[];
+  source?: string;
+  host?: string;
 }
 
 export interface SearchMatch {
   messageIndex: number;
+  uuid?: string;
+  offset: number;
   text: string;
   snippet: string;
 }
+
+export interface SearchTotals {
+  sessionsSearched: number;
+  sessionsMatched: number;
+  matches: number;
+  elapsedMs: number;
+}
+
+export interface TreeNode {
+  uuid: string;
+  parentUuid?: string;
+  type: "user" | "assistant";
+  timestamp?: string;
+  isSidechain: boolean;
+  onActiveBranch: boolean;
+  children: string[];
+  preview: string;
+  offset: number;
+}
+
+export interface ConversationTree {
+  nodes: TreeNode[];
+  roots: string[];
+  branchPoints: string[];
+  activeLeaf?: string;
+}
+
+export interface ToolCall {
+  toolUseId: string;
+  name?: string;
+  input?: Record<string, unknown>;
+  result?: string | ContentBlock[];
+  isError: boolean;
+  completed: boolean;
+  startedAt?: string;
+  endedAt?: string;
+  durationMs?: number;
+  messageUuid?: string;
+  resultUuid?: string;
+}
+
+export interface AnalyticsRow {
+  key: string;
+  label?: string;
+  inputTokens: number;
+  outputTokens: number;
+  cacheCreationInputTokens: number;
+  cacheReadInputTokens: number;
+  requests: number;
+  cost: number;
+}
+
+export interface AnalyticsResult {
+  rows: AnalyticsRow[];
+  total: AnalyticsRow;
+  unpricedModels: string[];
+}
+
+export interface ContextPoint {
+  turn: number;
+  uuid?: string;
+  timestamp?: string;
+  contextTokens: number;
+  outputTokens: number;
+  cacheReadTokens: number;
+  cacheCreationTokens: number;
+  cacheReadRatio: number;
+}
+
+export interface CompactionMarker {
+  turn: number;
+  offset: number;
+  timestamp?: string;
+  trigger?: string;
+  preTokens?: number;
+  summary?: string;
+}
+
+export interface ContextTimeline {
+  points: ContextPoint[];
+  compactions: CompactionMarker[];
+  peakContextTokens: number;
+  endOffset: number;
+}
+
+export interface TrashedSession {
+  id: string;
+  display: string;
+  project: string;
+  projectName: string;
+  deletedAt?: number;
+  messageCount: number;
+  fileSize?: number;
+}